@@ -3,7 +3,7 @@ use std::sync::{Mutex};
 use macroquad::prelude::{Vec2};
 use hecs::{World, Entity};
 
-use crate::KaSprite;
+use crate::{KaSprite, ka_transforms_update};
 
 
 const MARGIN: f32 = 0.01;
@@ -98,6 +98,17 @@ impl KaMoveable {
     
 }
 
+//Position of an entity from its KaAABB, or from its KaSprite without one.
+pub(crate) fn ka_entity_pos(world: &World, entity: Entity) -> Option<Vec2> {
+    if let Ok(aabb) = world.get::<KaAABB>(entity) {
+        return Some(*aabb.pos.lock().unwrap());
+    }
+    if let Ok(sprite) = world.get::<KaSprite>(entity) {
+        return Some(sprite.pos);
+    }
+    None
+}
+
 pub struct KaAABB{
     pub pos: Mutex<Vec2>,
     pub half_e: Vec2,
@@ -461,6 +472,7 @@ pub fn ka_aabb_sense( world: &mut World) {
 
 pub fn ka_aabb_update( world: &mut World, delta: f32) {
     ka_aabb_move(world, delta);
+    ka_transforms_update(world);
     ka_aabb_sense(world);
 }
 
@@ -0,0 +1,142 @@
+use std::convert::TryInto;
+
+use quad_snd::{
+    decoder::read_ogg,
+    mixer::{Sound, PlaybackStyle},
+};
+
+use crate::{KaError, KaResult};
+
+//The rate of the mixer, every sound is resampled to it when loaded.
+pub const KA_SAMPLE_RATE: u32 = 44100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KaAudioFormat {
+    Ogg,
+    Wav,
+}
+
+impl KaAudioFormat {
+    //From the first bytes of the file, the extension is not used.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"OggS") {
+            Some(KaAudioFormat::Ogg)
+        } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WAVE" {
+            Some(KaAudioFormat::Wav)
+        } else {
+            None
+        }
+    }
+}
+
+//Detects the format, decodes and resamples to KA_SAMPLE_RATE.
+//The name is only used in the errors.
+pub fn ka_decode_sound(name: &str, data: &[u8]) -> KaResult<Sound> {
+    let decode_error = |message: String| KaError::Decode {path: name.to_owned(), message};
+    let mut sound = match KaAudioFormat::detect(data) {
+        Some(KaAudioFormat::Ogg) => read_ogg(data).map_err(|e| decode_error(format!("invalid ogg data: {:?}", e)))?,
+        Some(KaAudioFormat::Wav) => read_wav(data).map_err(decode_error)?,
+        None => return Err(decode_error("unknown audio format".to_owned())),
+    };
+    ka_resample(&mut sound, KA_SAMPLE_RATE as f32);
+    Ok(sound)
+}
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+//PCM only, 8 or 16 bits, mono or stereo.
+pub fn read_wav(data: &[u8]) -> Result<Sound, String> {
+    if KaAudioFormat::detect(data) != Some(KaAudioFormat::Wav) {
+        return Err("not a wav file".to_owned());
+    }
+
+    //(channels, sample rate, bits per sample)
+    let mut format: Option<(u16, u32, u16)> = None;
+    let mut samples: Option<&[u8]> = None;
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let id = &data[pos..pos + 4];
+        let size = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let start = pos + 8;
+        //Some writers put a wrong size in the last chunk.
+        let end = start.saturating_add(size).min(data.len());
+        let chunk = &data[start..end];
+        match id {
+            b"fmt " => {
+                if chunk.len() < 16 {
+                    return Err("fmt chunk too short".to_owned());
+                }
+                let mut tag = u16::from_le_bytes([chunk[0], chunk[1]]);
+                if tag == WAVE_FORMAT_EXTENSIBLE && chunk.len() >= 26 {
+                    //The first two bytes of the sub format GUID.
+                    tag = u16::from_le_bytes([chunk[24], chunk[25]]);
+                }
+                if tag != WAVE_FORMAT_PCM {
+                    return Err(format!("unsupported wav format {}, only PCM", tag));
+                }
+                let channels = u16::from_le_bytes([chunk[2], chunk[3]]);
+                let sample_rate = u32::from_le_bytes(chunk[4..8].try_into().unwrap());
+                let bits = u16::from_le_bytes([chunk[14], chunk[15]]);
+                format = Some((channels, sample_rate, bits));
+            },
+            b"data" => samples = Some(chunk),
+            _ => (),
+        }
+        //The chunks are padded to an even size.
+        pos = start.saturating_add(size).saturating_add(size & 1);
+    }
+
+    let (channels, sample_rate, bits) = format.ok_or_else(|| "missing fmt chunk".to_owned())?;
+    let bytes = samples.ok_or_else(|| "missing data chunk".to_owned())?;
+    if channels != 1 && channels != 2 {
+        return Err(format!("unsupported channel count {}", channels));
+    }
+    if sample_rate == 0 {
+        return Err("invalid sample rate".to_owned());
+    }
+    let samples: Vec<f32> = match bits {
+        //8 bits are unsigned.
+        8 => bytes.iter().map(|&b| (b as f32 - 128.0) / 128.0).collect(),
+        16 => bytes.chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+            .collect(),
+        _ => return Err(format!("unsupported {} bits samples", bits)),
+    };
+
+    Ok(Sound {
+        sample_rate: sample_rate as f32,
+        channels,
+        samples,
+        playback_style: PlaybackStyle::Once,
+    })
+}
+
+//Linear interpolation, good enough for game sounds.
+pub fn ka_resample(sound: &mut Sound, sample_rate: f32) {
+    if sound.sample_rate == sample_rate || sound.sample_rate <= 0.0 || sound.channels == 0 {
+        return;
+    }
+    let channels = sound.channels as usize;
+    let frames = sound.samples.len() / channels;
+    if frames == 0 {
+        sound.sample_rate = sample_rate;
+        return;
+    }
+    let ratio = sound.sample_rate / sample_rate;
+    let new_frames = ((frames as f32) / ratio).round() as usize;
+    let mut samples = Vec::with_capacity(new_frames * channels);
+    for i in 0..new_frames {
+        let src = i as f32 * ratio;
+        let first = (src.floor() as usize).min(frames - 1);
+        let second = (first + 1).min(frames - 1);
+        let t = src - first as f32;
+        for c in 0..channels {
+            let a = sound.samples[first * channels + c];
+            let b = sound.samples[second * channels + c];
+            samples.push(a + (b - a) * t);
+        }
+    }
+    sound.samples = samples;
+    sound.sample_rate = sample_rate;
+}
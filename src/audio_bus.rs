@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+
+use quad_snd::mixer::{Sound, SoundId, SoundMixer, PlaybackStyle, Volume};
+
+use crate::{KaEase, KaTween, KaSpatial, ka_resample, KA_SAMPLE_RATE};
+
+pub const KA_BUS_MASTER: &str = "master";
+pub const KA_BUS_MUSIC: &str = "music";
+pub const KA_BUS_SFX: &str = "sfx";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KaBus {
+    pub volume: f32,
+    pub muted: bool,
+}
+
+impl Default for KaBus {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            muted: false,
+        }
+    }
+}
+
+impl KaBus {
+    pub fn get_volume(&self) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            self.volume
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KaPlayOptions {
+    pub bus: String,
+    pub volume: f32,
+    //1.0 is the normal speed, 2.0 is an octave higher and twice as fast.
+    pub pitch: f32,
+    //From -1.0 (left) to 1.0 (right). None can not be panned later but uses
+    //a single voice of the mixer, use it for the music and the long sounds.
+    //With a pan the sound is split in a left and a right voice, every play makes
+    //a stereo copy of the samples for each of them.
+    pub pan: Option<f32>,
+    //None keeps the looping of the loaded sound.
+    pub looped: Option<bool>,
+    //Seconds to go from silence to the volume.
+    pub fade_in: f32,
+}
+
+impl Default for KaPlayOptions {
+    fn default() -> Self {
+        Self {
+            bus: KA_BUS_SFX.to_owned(),
+            volume: 1.0,
+            pitch: 1.0,
+            pan: None,
+            looped: None,
+            fade_in: 0.0,
+        }
+    }
+}
+
+//Returned when playing a sound, stays valid after the sound ends
+//but does nothing anymore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KaSoundHandle(u64);
+
+struct KaFade {
+    from: f32,
+    to: f32,
+    tween: KaTween,
+    stop: bool,
+}
+
+struct KaVoice {
+    //One id, or the left and right ids when the voice can be panned. They are always
+    //stopped together, by stop, by the end of a fade_out or when remaining runs out.
+    ids: Vec<SoundId>,
+    bus: String,
+    volume: f32,
+    pan: f32,
+    fade: Option<KaFade>,
+    //Multiplied by the volume, changed by the fades.
+    gain: f32,
+    //Seconds left, the mixer does not tell when a sound ends. None when looped.
+    remaining: Option<f32>,
+}
+
+//Named buses with volume and mute, the sounds played with options and the music.
+//Every bus goes through the master bus.
+pub struct KaAudio {
+    //Used by KaGame::play_sound_at.
+    pub spatial: KaSpatial,
+    buses: HashMap<String, KaBus>,
+    voices: HashMap<u64, KaVoice>,
+    next_id: u64,
+    music: Option<(String, KaSoundHandle)>,
+}
+
+impl Default for KaAudio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KaAudio {
+    pub fn new() -> Self {
+        let mut buses = HashMap::new();
+        buses.insert(KA_BUS_MASTER.to_owned(), KaBus::default());
+        buses.insert(KA_BUS_MUSIC.to_owned(), KaBus::default());
+        buses.insert(KA_BUS_SFX.to_owned(), KaBus::default());
+        Self {
+            spatial: KaSpatial::default(),
+            buses,
+            voices: HashMap::new(),
+            next_id: 0,
+            music: None,
+        }
+    }
+
+    pub fn add_bus(&mut self, name: &str) {
+        self.buses.entry(name.to_owned()).or_insert_with(KaBus::default);
+    }
+
+    pub fn get_bus(&self, name: &str) -> Option<&KaBus> {
+        self.buses.get(name)
+    }
+
+    pub fn set_bus_volume(&mut self, name: &str, volume: f32) {
+        self.buses.entry(name.to_owned()).or_insert_with(KaBus::default).volume = volume.max(0.0);
+    }
+
+    pub fn get_bus_volume(&self, name: &str) -> f32 {
+        self.buses.get(name).map_or(1.0, |b| b.volume)
+    }
+
+    pub fn set_bus_muted(&mut self, name: &str, muted: bool) {
+        self.buses.entry(name.to_owned()).or_insert_with(KaBus::default).muted = muted;
+    }
+
+    pub fn is_bus_muted(&self, name: &str) -> bool {
+        self.buses.get(name).map_or(false, |b| b.muted)
+    }
+
+    fn get_bus_gain(&self, name: &str) -> f32 {
+        let master = self.buses.get(KA_BUS_MASTER).map_or(1.0, |b| b.get_volume());
+        if name == KA_BUS_MASTER {
+            return master;
+        }
+        master * self.buses.get(name).map_or(1.0, |b| b.get_volume())
+    }
+
+    pub fn play(&mut self, mixer: &mut SoundMixer, sound: &Sound, options: &KaPlayOptions) -> KaSoundHandle {
+        let style = match options.looped {
+            Some(true) => PlaybackStyle::Looped,
+            Some(false) => PlaybackStyle::Once,
+            None => sound.playback_style.clone(),
+        };
+        //Only copied here when the pitch changes, the split makes its own copies.
+        let pitched = if options.pitch > 0.0 && options.pitch != 1.0 {
+            let mut pitched = sound.clone();
+            pitched.sample_rate = KA_SAMPLE_RATE as f32 * options.pitch;
+            ka_resample(&mut pitched, KA_SAMPLE_RATE as f32);
+            Some(pitched)
+        } else {
+            None
+        };
+        let source = pitched.as_ref().unwrap_or(sound);
+        let channels = source.channels.max(1) as usize;
+        let remaining = match style {
+            PlaybackStyle::Looped => None,
+            PlaybackStyle::Once => Some(source.samples.len() as f32 / channels as f32 / KA_SAMPLE_RATE as f32),
+        };
+
+        let ids = match options.pan {
+            //Each side is its own voice so the volumes can be changed separately.
+            Some(_) => {
+                let (left, right) = split_stereo(source, style);
+                vec![mixer.play(left), mixer.play(right)]
+            },
+            None => {
+                let mut sound = pitched.unwrap_or_else(|| sound.clone());
+                sound.playback_style = style;
+                vec![mixer.play(sound)]
+            },
+        };
+
+        let fade = if options.fade_in > 0.0 {
+            Some(KaFade {
+                from: 0.0,
+                to: 1.0,
+                tween: KaTween::new(options.fade_in, KaEase::Linear),
+                stop: false,
+            })
+        } else {
+            None
+        };
+        let voice = KaVoice {
+            ids,
+            bus: options.bus.clone(),
+            volume: options.volume,
+            pan: options.pan.unwrap_or(0.0).max(-1.0).min(1.0),
+            gain: if fade.is_some() {0.0} else {1.0},
+            fade,
+            remaining,
+        };
+        self.apply_volume(mixer, &voice);
+
+        let handle = KaSoundHandle(self.next_id);
+        self.next_id += 1;
+        self.voices.insert(handle.0, voice);
+        handle
+    }
+
+    pub fn stop(&mut self, mixer: &mut SoundMixer, handle: KaSoundHandle) {
+        if let Some(voice) = self.voices.remove(&handle.0) {
+            for id in voice.ids {
+                mixer.stop(id);
+            }
+        }
+    }
+
+    pub fn stop_all(&mut self, mixer: &mut SoundMixer) {
+        for (_, voice) in self.voices.drain() {
+            for id in voice.ids {
+                mixer.stop(id);
+            }
+        }
+        self.music = None;
+    }
+
+    pub fn is_playing(&self, handle: KaSoundHandle) -> bool {
+        self.voices.contains_key(&handle.0)
+    }
+
+    pub fn set_volume(&mut self, handle: KaSoundHandle, volume: f32) {
+        if let Some(voice) = self.voices.get_mut(&handle.0) {
+            voice.volume = volume;
+        }
+    }
+
+    //Only for the sounds played with a pan.
+    pub fn set_pan(&mut self, handle: KaSoundHandle, pan: f32) {
+        if let Some(voice) = self.voices.get_mut(&handle.0) {
+            voice.pan = pan.max(-1.0).min(1.0);
+        }
+    }
+
+    //Changes the gain from the current one, the volume set with set_volume is kept.
+    pub fn fade_to(&mut self, handle: KaSoundHandle, gain: f32, duration: f32) {
+        self.fade(handle, gain, duration, false);
+    }
+
+    //The sound is stopped at the end of the fade.
+    pub fn fade_out(&mut self, handle: KaSoundHandle, duration: f32) {
+        self.fade(handle, 0.0, duration, true);
+    }
+
+    fn fade(&mut self, handle: KaSoundHandle, to: f32, duration: f32, stop: bool) {
+        if let Some(voice) = self.voices.get_mut(&handle.0) {
+            voice.fade = Some(KaFade {
+                from: voice.gain,
+                to,
+                tween: KaTween::new(duration, KaEase::Linear),
+                stop,
+            });
+        }
+    }
+
+    //Loops the track on the music bus, the current one fades out while the new one fades in.
+    //Playing the track already playing does nothing.
+    pub fn play_music(&mut self, mixer: &mut SoundMixer, name: &str, sound: &Sound, crossfade: f32) {
+        if self.get_music() == Some(name) {
+            return;
+        }
+        self.stop_music(mixer, crossfade);
+        let handle = self.play(mixer, sound, &KaPlayOptions {
+            bus: KA_BUS_MUSIC.to_owned(),
+            looped: Some(true),
+            fade_in: crossfade,
+            ..Default::default()
+        });
+        self.music = Some((name.to_owned(), handle));
+    }
+
+    pub fn stop_music(&mut self, mixer: &mut SoundMixer, fade: f32) {
+        if let Some((_, handle)) = self.music.take() {
+            if fade > 0.0 {
+                self.fade_out(handle, fade);
+            } else {
+                self.stop(mixer, handle);
+            }
+        }
+    }
+
+    pub fn get_music(&self) -> Option<&str> {
+        self.music.as_ref().map(|(name, _)| name.as_str())
+    }
+
+    pub fn get_music_handle(&self) -> Option<KaSoundHandle> {
+        self.music.as_ref().map(|(_, handle)| *handle)
+    }
+
+    //Call it every frame, runs the fades, applies the bus volumes and forgets the finished sounds.
+    pub fn update(&mut self, mixer: &mut SoundMixer, delta: f32) {
+        let mut finished = Vec::new();
+        for (id, voice) in self.voices.iter_mut() {
+            if let Some(fade) = &mut voice.fade {
+                fade.tween.update(delta);
+                voice.gain = fade.tween.lerp(fade.from, fade.to);
+                if fade.tween.is_finished() {
+                    if fade.stop {
+                        finished.push(*id);
+                    }
+                    voice.fade = None;
+                }
+            }
+            if let Some(remaining) = &mut voice.remaining {
+                *remaining -= delta;
+                if *remaining <= 0.0 {
+                    finished.push(*id);
+                }
+            }
+        }
+        for id in finished {
+            self.stop(mixer, KaSoundHandle(id));
+        }
+        for voice in self.voices.values() {
+            self.apply_volume(mixer, voice);
+        }
+        if let Some((_, handle)) = self.music {
+            if !self.is_playing(handle) {
+                self.music = None;
+            }
+        }
+    }
+
+    fn apply_volume(&self, mixer: &mut SoundMixer, voice: &KaVoice) {
+        let volume = voice.volume * voice.gain * self.get_bus_gain(&voice.bus);
+        match voice.ids.as_slice() {
+            [left, right] => {
+                //Balance, the center keeps both sides at full volume.
+                mixer.set_volume(*left, Volume(volume * (1.0 - voice.pan).min(1.0)));
+                mixer.set_volume(*right, Volume(volume * (1.0 + voice.pan).min(1.0)));
+            },
+            ids => {
+                for id in ids {
+                    mixer.set_volume(*id, Volume(volume));
+                }
+            },
+        }
+    }
+}
+
+//Two stereo sounds, one with only the left side and one with only the right.
+fn split_stereo(sound: &Sound, playback_style: PlaybackStyle) -> (Sound, Sound) {
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+    if sound.channels == 1 {
+        left.reserve(sound.samples.len() * 2);
+        right.reserve(sound.samples.len() * 2);
+        for &sample in &sound.samples {
+            left.extend_from_slice(&[sample, 0.0]);
+            right.extend_from_slice(&[0.0, sample]);
+        }
+    } else {
+        left.reserve(sound.samples.len());
+        right.reserve(sound.samples.len());
+        for frame in sound.samples.chunks_exact(2) {
+            left.extend_from_slice(&[frame[0], 0.0]);
+            right.extend_from_slice(&[0.0, frame[1]]);
+        }
+    }
+    let side = |samples: Vec<f32>| Sound {
+        sample_rate: sound.sample_rate,
+        channels: 2,
+        samples,
+        playback_style: playback_style.clone(),
+    };
+    (side(left), side(right))
+}
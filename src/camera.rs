@@ -1,11 +1,211 @@
-use macroquad::prelude::{Vec2, Camera2D, set_camera};
+use macroquad::prelude::{Vec2, Camera2D, Rect, RenderTarget, set_camera};
+use hecs::{World, Entity};
+use noise::{Perlin, NoiseFn, Seedable};
 
+use crate::{KaMoveable, KaEase, KaTween, ka_entity_pos};
+
+
+pub struct KaCameraFollow {
+    pub target: Entity,
+    //Half extents of the rect around the camera center where the target can move without moving the camera.
+    pub dead_zone: Vec2,
+    //Seconds of the target velocity the camera looks ahead.
+    pub look_ahead: f32,
+    //Exponential smoothing speed, 0.0 snaps to the target.
+    pub smoothing: f32,
+    //Rounds the final position to whole pixels to avoid shimmering with FilterMode::Nearest.
+    pub pixel_snap: bool,
+    goal: Option<Vec2>,
+    precise_pos: Option<Vec2>,
+}
+
+impl KaCameraFollow {
+    pub fn new(target: Entity) -> Self {
+        Self {
+            target,
+            dead_zone: Vec2::zero(),
+            look_ahead: 0.0,
+            smoothing: 0.0,
+            pixel_snap: false,
+            goal: None,
+            precise_pos: None,
+        }
+    }
+
+    //Position of the target from its KaAABB or its KaSprite.
+    pub fn get_target_pos(&self, world: &World) -> Option<Vec2> {
+        ka_entity_pos(world, self.target)
+    }
+
+    pub fn get_target_velocity(&self, world: &World) -> Vec2 {
+        match world.get::<KaMoveable>(self.target) {
+            Ok(moveable) => moveable.velocity + moveable.external_forces,
+            Err(_) => Vec2::zero(),
+        }
+    }
+
+    //Returns the new camera position.
+    pub fn update(&mut self, world: &World, camera_pos: Vec2, delta: f32) -> Vec2 {
+        let current = self.precise_pos.unwrap_or(camera_pos);
+        let target = match self.get_target_pos(world) {
+            Some(pos) => pos + self.get_target_velocity(world) * self.look_ahead,
+            None => return camera_pos,
+        };
+
+        let mut goal = self.goal.unwrap_or(target);
+        let diff = target - goal;
+        if diff.x().abs() > self.dead_zone.x() {
+            *goal.x_mut() = target.x() - self.dead_zone.x() * diff.x().signum();
+        }
+        if diff.y().abs() > self.dead_zone.y() {
+            *goal.y_mut() = target.y() - self.dead_zone.y() * diff.y().signum();
+        }
+        self.goal = Some(goal);
+
+        let pos = if self.smoothing > 0.0 {
+            current.lerp(goal, 1.0 - (-self.smoothing * delta).exp())
+        } else {
+            goal
+        };
+        self.precise_pos = Some(pos);
+        if self.pixel_snap {
+            Vec2::new(pos.x().round(), pos.y().round())
+        } else {
+            pos
+        }
+    }
+
+    //Forgets the smoothing state, the next update starts from the camera position.
+    pub fn reset(&mut self) {
+        self.goal = None;
+        self.precise_pos = None;
+    }
+}
+
+//Metroidvania like rooms, the camera stays inside the room of the followed entity
+//and pans to the next one when the entity enters it.
+pub struct KaCameraRooms {
+    pub rooms: Vec<Rect>,
+    pub transition_time: f32,
+    pub ease: KaEase,
+    //The game should skip its update while is_paused returns true.
+    pub pause_gameplay: bool,
+    current: Option<usize>,
+    transition: Option<(Vec2, f32)>,
+}
+
+impl KaCameraRooms {
+    pub fn new(rooms: Vec<Rect>, transition_time: f32) -> Self {
+        Self {
+            rooms,
+            transition_time,
+            ease: KaEase::QuadInOut,
+            pause_gameplay: true,
+            current: None,
+            transition: None,
+        }
+    }
+
+    pub fn get_room_at(&self, pos: Vec2) -> Option<usize> {
+        self.rooms.iter().position(|room| room.contains(pos))
+    }
+
+    pub fn get_current_room(&self) -> Option<usize> {
+        self.current
+    }
+
+    pub fn is_transitioning(&self) -> bool {
+        self.transition.is_some()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pause_gameplay && self.is_transitioning()
+    }
+}
+
+//Trauma based screen shake, the shake is trauma squared and trauma goes down with time.
+pub struct KaCameraShake {
+    pub trauma: f32,
+    //Trauma removed per second.
+    pub decay: f32,
+    pub max_offset: Vec2,
+    //In degrees.
+    pub max_angle: f32,
+    //Speed of the noise, higher is more jittery.
+    pub frequency: f32,
+    time: f32,
+    noise: Perlin,
+    offset: Vec2,
+    angle: f32,
+}
+
+impl KaCameraShake {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            trauma: 0.0,
+            decay: 1.0,
+            max_offset: Vec2::new(8.0, 8.0),
+            max_angle: 3.0,
+            frequency: 25.0,
+            time: 0.0,
+            noise: Perlin::new().set_seed(seed),
+            offset: Vec2::zero(),
+            angle: 0.0,
+        }
+    }
+
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).max(0.0).min(1.0);
+    }
+
+    fn get_noise(&self, channel: f64) -> f32 {
+        self.noise.get([channel * 17.0, (self.time * self.frequency) as f64]) as f32
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        self.time += delta;
+        self.trauma = (self.trauma - self.decay * delta).max(0.0);
+        let shake = self.trauma * self.trauma;
+        self.offset = Vec2::new(
+            self.max_offset.x() * shake * self.get_noise(1.0),
+            self.max_offset.y() * shake * self.get_noise(2.0),
+        );
+        self.angle = self.max_angle * shake * self.get_noise(3.0);
+    }
+
+    pub fn get_offset(&self) -> Vec2 {
+        self.offset
+    }
+
+    pub fn get_angle(&self) -> f32 {
+        self.angle
+    }
+}
 
 pub struct KaCamera {
     width: f32,
     height: f32,
     scale: f32,
+    pos: Vec2,
+    zoom: f32,
+    //In degrees like Camera2D.
+    rotation: f32,
+    pos_tween: Option<(Vec2, Vec2, KaTween)>,
+    zoom_tween: Option<(f32, f32, KaTween)>,
+    rotation_tween: Option<(f32, f32, KaTween)>,
+    //Rebuilt from the position, zoom, rotation and shake by refresh.
     pub camera: Camera2D,
+    pub shake: KaCameraShake,
+    //Keeps the aspect of width and height with bars instead of stretching to the window.
+    pub letterbox: bool,
+    //Part of the screen used by the camera, from (0, 0, 1, 1) for the whole screen. Used for split screen.
+    pub viewport: Option<Rect>,
+    //Inactive cameras are not drawn by ka_draw_cameras and do not activate placeholders in placeholder::system_cameras.
+    pub active: bool,
+    pub follow: Option<KaCameraFollow>,
+    //The view is kept inside the bounds.
+    pub bounds: Option<Rect>,
+    pub rooms: Option<KaCameraRooms>,
 }
 
 impl KaCamera {
@@ -15,71 +215,525 @@ impl KaCamera {
             width,
             height,
             scale,
+            pos: Vec2::zero(),
+            zoom: 1.0,
+            rotation: 0.0,
+            pos_tween: None,
+            zoom_tween: None,
+            rotation_tween: None,
             camera: Camera2D 
                 {
                     offset: Vec2::zero(),
                     zoom: Vec2::new(scale, -2.0 / height),
                     ..Default::default()
+                },
+            shake: KaCameraShake::new(0),
+            letterbox: false,
+            viewport: None,
+            active: true,
+            follow: None,
+            bounds: None,
+            rooms: None,
+        }
+    }
+
+    //Builds the Camera2D, the shake only affects the rendering, not get_pos.
+    pub fn refresh(&mut self) {
+        self.scale = 2.0 / self.get_width();
+        self.camera.target = self.pos + self.shake.get_offset();
+        self.camera.offset = Vec2::zero();
+        self.camera.zoom = Vec2::new(self.scale, -2.0 / self.get_height());
+        self.camera.rotation = self.rotation + self.shake.get_angle();
+    }
+
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.shake.add_trauma(amount);
+    }
+
+    pub fn get_zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    //2.0 shows half the width and height.
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom_tween = None;
+        self.zoom = zoom.max(0.001);
+        self.refresh();
+    }
+
+    pub fn zoom_to(&mut self, zoom: f32, duration: f32, ease: KaEase) {
+        self.zoom_tween = Some((self.zoom, zoom.max(0.001), KaTween::new(duration, ease)));
+    }
+
+    pub fn get_rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.rotation_tween = None;
+        self.rotation = rotation;
+        self.refresh();
+    }
+
+    pub fn rotate_to(&mut self, rotation: f32, duration: f32, ease: KaEase) {
+        self.rotation_tween = Some((self.rotation, rotation, KaTween::new(duration, ease)));
+    }
+
+    //While moving the camera does not follow.
+    pub fn move_to(&mut self, pos: Vec2, duration: f32, ease: KaEase) {
+        self.pos_tween = Some((self.pos, pos, KaTween::new(duration, ease)));
+    }
+
+    pub fn is_tweening(&self) -> bool {
+        self.pos_tween.is_some() || self.zoom_tween.is_some() || self.rotation_tween.is_some()
+    }
+
+    fn update_tweens(&mut self, delta: f32) {
+        if let Some((from, to, mut tween)) = self.zoom_tween.take() {
+            tween.update(delta);
+            self.zoom = tween.lerp(from, to);
+            if !tween.is_finished() {
+                self.zoom_tween = Some((from, to, tween));
+            }
+        }
+        if let Some((from, to, mut tween)) = self.rotation_tween.take() {
+            tween.update(delta);
+            self.rotation = tween.lerp(from, to);
+            if !tween.is_finished() {
+                self.rotation_tween = Some((from, to, tween));
+            }
+        }
+        if let Some((from, to, mut tween)) = self.pos_tween.take() {
+            tween.update(delta);
+            self.pos = from.lerp(to, tween.get_t());
+            if !tween.is_finished() {
+                self.pos_tween = Some((from, to, tween));
+            } else if let Some(follow) = &mut self.follow {
+                //The follow starts again from where the tween ended.
+                follow.reset();
+            }
+        }
+    }
+
+    pub fn set_bounds(&mut self, bounds: Option<Rect>) {
+        self.bounds = bounds;
+    }
+
+    pub fn set_rooms(&mut self, rooms: Option<KaCameraRooms>) {
+        self.rooms = rooms;
+    }
+
+    //Returns pos moved so the view stays inside bounds, if the bounds are smaller than the view it is centered.
+    pub fn clamp_to(&self, pos: Vec2, bounds: &Rect) -> Vec2 {
+        let half_e = Vec2::new(self.get_width() / 2.0, self.get_height() / 2.0);
+        let clamp = |v: f32, min: f32, size: f32, half: f32| {
+            if size <= half * 2.0 {
+                min + size / 2.0
+            } else {
+                v.max(min + half).min(min + size - half)
+            }
+        };
+        Vec2::new(
+            clamp(pos.x(), bounds.x, bounds.w, half_e.x()),
+            clamp(pos.y(), bounds.y, bounds.h, half_e.y()),
+        )
+    }
+
+    pub fn clamp_pos(&self, pos: Vec2) -> Vec2 {
+        match &self.bounds {
+            Some(bounds) => self.clamp_to(pos, bounds),
+            None => pos,
+        }
+    }
+
+    //true while a room transition wants the gameplay paused.
+    pub fn is_paused(&self) -> bool {
+        match &self.rooms {
+            Some(rooms) => rooms.is_paused(),
+            None => false,
+        }
+    }
+
+    pub fn follow(&mut self, follow: KaCameraFollow) {
+        self.follow = Some(follow);
+    }
+
+    pub fn stop_following(&mut self) {
+        self.follow = None;
+    }
+
+    //Updates the tweens and the shake and moves the camera towards the followed entity,
+    //keeping it inside the bounds and rooms.
+    pub fn update(&mut self, world: &World, delta: f32) {
+        let moving = self.pos_tween.is_some();
+        self.update_tweens(delta);
+        self.shake.update(delta);
+
+        let current_pos = self.get_pos();
+        let mut pos = current_pos;
+        let mut target_pos = current_pos;
+        if let Some(follow) = &mut self.follow {
+            if !moving {
+                pos = follow.update(world, current_pos, delta);
+            }
+            target_pos = follow.get_target_pos(world).unwrap_or(pos);
+        }
+
+        if let Some(mut rooms) = self.rooms.take() {
+            if let Some(room) = rooms.get_room_at(target_pos) {
+                if rooms.current != Some(room) {
+                    if rooms.current.is_some() && rooms.transition_time > 0.0 {
+                        rooms.transition = Some((current_pos, 0.0));
+                    }
+                    rooms.current = Some(room);
+                    self.bounds = Some(rooms.rooms[room]);
+                }
+            }
+            pos = self.clamp_pos(pos);
+            if let Some((from, timer)) = rooms.transition {
+                let timer = timer + delta;
+                if timer >= rooms.transition_time {
+                    rooms.transition = None;
+                } else {
+                    let t = rooms.ease.apply(timer / rooms.transition_time);
+                    pos = from.lerp(pos, t);
+                    rooms.transition = Some((from, timer));
                 }
+            }
+            self.rooms = Some(rooms);
+        } else {
+            pos = self.clamp_pos(pos);
         }
+
+        self.pos = pos;
+        self.refresh();
+    }
+
+    //Pixel rect of the screen where the view is drawn, with letterbox the aspect of the view is kept.
+    pub fn get_screen_rect(&self, screen_width: f32, screen_height: f32) -> Rect {
+        let area = match self.viewport {
+            Some(viewport) => Rect::new(
+                viewport.x * screen_width, viewport.y * screen_height,
+                viewport.w * screen_width, viewport.h * screen_height,
+            ),
+            None => Rect::new(0.0, 0.0, screen_width, screen_height),
+        };
+        if !self.letterbox {
+            return area;
+        }
+        let scale = (area.w / self.width).min(area.h / self.height);
+        let (w, h) = (self.width * scale, self.height * scale);
+        Rect::new((area.x + (area.w - w) / 2.0).floor(), (area.y + (area.h - h) / 2.0).floor(), w, h)
+    }
+
+    pub fn set_viewport(&mut self, x: f32, y: f32, w: f32, h: f32) {
+        self.viewport = Some(Rect::new(x, y, w, h));
+    }
+
+    //Same transformation as Camera2D: zoom * rotation * (pos - target).
+    pub fn world_to_screen_in(&self, pos: Vec2, screen_rect: Rect) -> Vec2 {
+        let d = pos - self.camera.target;
+        let (sin, cos) = self.camera.rotation.to_radians().sin_cos();
+        let d = Vec2::new(d.x() * cos - d.y() * sin, d.x() * sin + d.y() * cos);
+        let clip = d * self.camera.zoom + self.camera.offset;
+        Vec2::new(
+            screen_rect.x + (clip.x() + 1.0) / 2.0 * screen_rect.w,
+            screen_rect.y + (1.0 - clip.y()) / 2.0 * screen_rect.h,
+        )
+    }
+
+    pub fn screen_to_world_in(&self, pos: Vec2, screen_rect: Rect) -> Vec2 {
+        let clip = Vec2::new(
+            (pos.x() - screen_rect.x) / screen_rect.w * 2.0 - 1.0,
+            1.0 - (pos.y() - screen_rect.y) / screen_rect.h * 2.0,
+        );
+        let d = (clip - self.camera.offset) / self.camera.zoom;
+        let (sin, cos) = self.camera.rotation.to_radians().sin_cos();
+        let d = Vec2::new(d.x() * cos + d.y() * sin, -d.x() * sin + d.y() * cos);
+        d + self.camera.target
     }
 
     pub fn screen_to_world(&self, pos: (f32, f32)) -> Vec2 {
-        use macroquad::prelude::{screen_width, screen_height, vec2};
-        let screen_size = vec2(screen_width(), screen_height());
-        let mut pos = Vec2::new(pos.0, pos.1);
-        pos -= screen_size / 2.0;
-        pos /= screen_size * self.scale;
-        pos   
+        use macroquad::prelude::{screen_width, screen_height};
+        let screen_rect = self.get_screen_rect(screen_width(), screen_height());
+        self.screen_to_world_in(Vec2::new(pos.0, pos.1), screen_rect)
+    }
+
+    pub fn world_to_screen(&self, pos: Vec2) -> Vec2 {
+        use macroquad::prelude::{screen_width, screen_height};
+        let screen_rect = self.get_screen_rect(screen_width(), screen_height());
+        self.world_to_screen_in(pos, screen_rect)
     }
 
     pub fn set(&self) {
-        set_camera(self.camera);
+        set_camera(self.apply_viewport(self.camera));
+    }
+
+    //Restricts camera to the part of the screen of the viewport and the letterbox.
+    fn apply_viewport(&self, mut camera: Camera2D) -> Camera2D {
+        use macroquad::prelude::{screen_width, screen_height};
+        if self.letterbox || self.viewport.is_some() {
+            let rect = self.get_screen_rect(screen_width(), screen_height());
+            //The gl viewport starts from the bottom of the screen.
+            let y = screen_height() - rect.y - rect.h;
+            camera.viewport = Some((rect.x as i32, y as i32, rect.w as i32, rect.h as i32));
+        }
+        camera
     }
 
     pub fn get_scale(&self) -> f32 {
         self.scale
     }
 
+    //Width of the view in world units, it changes with the zoom.
     pub fn get_width(&self) -> f32 {
-        self.width
+        self.width / self.zoom
     }
 
     pub fn get_height(&self) -> f32 {
-        self.height
+        self.height / self.zoom
     }
 
     pub fn get_pos(&self) -> Vec2 {
-        -self.camera.offset / self.scale
+        self.pos
     }
 
+    //The follow smoothing starts again from pos.
     pub fn set_pos_v(&mut self, pos: Vec2) {
-        self.camera.offset = -pos * self.scale;
+        self.pos = pos;
+        if let Some(follow) = &mut self.follow {
+            follow.reset();
+        }
+        self.refresh();
     }
 
     pub fn set_pos_xy(&mut self, x: f32, y: f32) {
-        self.camera.offset.set_x(-x * self.scale);
-        self.camera.offset.set_y(-y * self.scale);
+        self.set_pos_v(Vec2::new(x, y));
     }
 
     pub fn move_v(&mut self, movement: Vec2) {
-        *self.camera.offset.x_mut() -= movement.x() * self.scale;
-        *self.camera.offset.y_mut() -= movement.y() * self.scale;
+        self.set_pos_v(self.pos + movement);
     }
 
     pub fn move_xy(&mut self, x: f32, y: f32) {
-        *self.camera.offset.x_mut() -= x * self.scale;
-        *self.camera.offset.y_mut() -= y * self.scale;
+        self.set_pos_v(self.pos + Vec2::new(x, y));
     }
 
     pub fn get_camera(&self) -> Camera2D {
         self.camera
     }
 
+    //The camera drawing in a render target instead of the screen.
+    pub fn get_target_camera(&self, target: RenderTarget) -> Camera2D {
+        ka_flip_for_target(self.camera, target)
+    }
+
+    //A copy of the camera that moves factor times the camera movement, (0, 0) stays fixed to the screen.
+    //It draws in target when given, like get_target_camera, else in the same viewport as set.
+    pub fn get_parallax_camera(&self, factor: Vec2, target: Option<RenderTarget>) -> Camera2D {
+        let mut camera = self.camera;
+        camera.target = self.pos * factor + self.shake.get_offset();
+        match target {
+            Some(target) => ka_flip_for_target(camera, target),
+            None => self.apply_viewport(camera),
+        }
+    }
+
+    //Center and half extents of the visible area, with rotation it is the box around the rotated view.
     pub fn get_aabb_data(&self) -> (Vec2, Vec2) {
         let pos = self.get_pos();
-        let half_e = Vec2::new(self.width / 2.0, self.height / 2.0);
+        let (w, h) = (self.get_width() / 2.0, self.get_height() / 2.0);
+        let (sin, cos) = self.camera.rotation.to_radians().sin_cos();
+        let half_e = Vec2::new(w * cos.abs() + h * sin.abs(), w * sin.abs() + h * cos.abs());
         (pos, half_e)
     }
 
-}
\ No newline at end of file
+}
+
+//The render target is upside down.
+fn ka_flip_for_target(mut camera: Camera2D, target: RenderTarget) -> Camera2D {
+    *camera.zoom.y_mut() = -camera.zoom.y();
+    camera.render_target = Some(target);
+    camera
+}
+
+//Camera that draws rect in the whole render target, used by the passes between render targets.
+pub fn ka_render_target_camera(rect: Rect, target: RenderTarget) -> Camera2D {
+    ka_flip_for_target(Camera2D::from_display_rect(rect), target)
+}
+
+//Calls draw_fn once for every active camera with the camera set, draw_fn usually calls ka_draw_sprites.
+pub fn ka_draw_cameras(world: &mut World, cameras: &[KaCamera], mut draw_fn: impl FnMut(&mut World, &KaCamera)) {
+    for camera in cameras.iter() {
+        if camera.active {
+            camera.set();
+            draw_fn(world, camera);
+        }
+    }
+}
+
+//Two cameras side by side or, with vertical, one over the other.
+pub fn ka_split_screen_2(cameras: &mut [KaCamera], vertical: bool) {
+    for (i, camera) in cameras.iter_mut().take(2).enumerate() {
+        let i = i as f32;
+        if vertical {
+            camera.set_viewport(0.0, i * 0.5, 1.0, 0.5);
+        } else {
+            camera.set_viewport(i * 0.5, 0.0, 0.5, 1.0);
+        }
+    }
+}
+
+//Up to four cameras in the corners of the screen.
+pub fn ka_split_screen_4(cameras: &mut [KaCamera]) {
+    for (i, camera) in cameras.iter_mut().take(4).enumerate() {
+        let (x, y) = ((i % 2) as f32 * 0.5, (i / 2) as f32 * 0.5);
+        camera.set_viewport(x, y, 0.5, 0.5);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN_SIZES: [(f32, f32); 4] = [(800.0, 600.0), (1920.0, 1080.0), (333.0, 777.0), (1.0, 1.0)];
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!((a - b).length() < 0.01 * b.length().max(1.0), "{:?} != {:?}", a, b);
+    }
+
+    fn assert_px(a: Vec2, b: Vec2) {
+        assert!((a - b).length() < 0.01, "{:?} != {:?}", a, b);
+    }
+
+    fn get_cameras() -> Vec<KaCamera> {
+        let plain = KaCamera::new(320.0, 180.0);
+
+        let mut moved = KaCamera::new(320.0, 180.0);
+        moved.set_pos_xy(150.0, -40.0);
+        moved.set_zoom(2.0);
+        moved.set_rotation(30.0);
+
+        let mut letterboxed = KaCamera::new(320.0, 180.0);
+        letterboxed.set_pos_xy(-75.5, 300.25);
+        letterboxed.set_zoom(0.5);
+        letterboxed.set_rotation(-90.0);
+        letterboxed.letterbox = true;
+        letterboxed.set_viewport(0.5, 0.0, 0.5, 1.0);
+
+        vec![plain, moved, letterboxed]
+    }
+
+    #[test]
+    fn world_to_screen_round_trip() {
+        let points = [Vec2::zero(), Vec2::new(10.0, 20.0), Vec2::new(-160.0, 90.0), Vec2::new(1000.0, -500.0)];
+        for camera in get_cameras().iter() {
+            for (w, h) in SCREEN_SIZES.iter() {
+                let screen_rect = camera.get_screen_rect(*w, *h);
+                for point in points.iter() {
+                    let screen = camera.world_to_screen_in(*point, screen_rect);
+                    assert_close(camera.screen_to_world_in(screen, screen_rect), *point);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn screen_to_world_round_trip() {
+        for camera in get_cameras().iter() {
+            for (w, h) in SCREEN_SIZES.iter() {
+                let screen_rect = camera.get_screen_rect(*w, *h);
+                let points = [
+                    Vec2::new(screen_rect.x, screen_rect.y),
+                    Vec2::new(screen_rect.x + screen_rect.w, screen_rect.y + screen_rect.h),
+                    Vec2::new(screen_rect.x + screen_rect.w * 0.25, screen_rect.y + screen_rect.h * 0.75),
+                ];
+                for point in points.iter() {
+                    let world = camera.screen_to_world_in(*point, screen_rect);
+                    assert_close(camera.world_to_screen_in(world, screen_rect), *point);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn camera_pos_is_screen_center() {
+        for camera in get_cameras().iter() {
+            for (w, h) in SCREEN_SIZES.iter() {
+                let screen_rect = camera.get_screen_rect(*w, *h);
+                let center = Vec2::new(screen_rect.x + screen_rect.w / 2.0, screen_rect.y + screen_rect.h / 2.0);
+                assert_close(camera.world_to_screen_in(camera.get_pos(), screen_rect), center);
+            }
+        }
+    }
+
+    //Expected values worked out by hand from Camera2D: zoom * rotation * (pos - target), y down.
+    #[test]
+    fn world_to_screen_edges() {
+        let mut camera = KaCamera::new(320.0, 180.0);
+        camera.set_pos_xy(150.0, -40.0);
+        camera.set_zoom(2.0);
+        let rect = camera.get_screen_rect(800.0, 600.0);
+        let pos = camera.get_pos();
+        let (w, h) = (camera.get_width() / 2.0, camera.get_height() / 2.0);
+        assert_px(camera.world_to_screen_in(pos + Vec2::new(w, 0.0), rect), Vec2::new(800.0, 300.0));
+        assert_px(camera.world_to_screen_in(pos - Vec2::new(w, 0.0), rect), Vec2::new(0.0, 300.0));
+        assert_px(camera.world_to_screen_in(pos + Vec2::new(0.0, h), rect), Vec2::new(400.0, 600.0));
+        assert_px(camera.world_to_screen_in(pos - Vec2::new(w, h), rect), Vec2::new(0.0, 0.0));
+        assert_px(camera.screen_to_world_in(Vec2::new(800.0, 600.0), rect), pos + Vec2::new(w, h));
+    }
+
+    #[test]
+    fn world_to_screen_rotation() {
+        let mut camera = KaCamera::new(320.0, 180.0);
+        camera.set_pos_xy(10.0, 20.0);
+        camera.set_rotation(90.0);
+        let rect = camera.get_screen_rect(320.0, 180.0);
+        //Rotated by 90 degrees the right of the world goes to the bottom of the screen.
+        assert_px(camera.world_to_screen_in(Vec2::new(100.0, 20.0), rect), Vec2::new(160.0, 180.0));
+        //And the bottom of the world to the left of the screen.
+        assert_px(camera.world_to_screen_in(Vec2::new(10.0, 180.0), rect), Vec2::new(0.0, 90.0));
+        assert_px(camera.screen_to_world_in(Vec2::new(160.0, 0.0), rect), Vec2::new(-80.0, 20.0));
+    }
+
+    #[test]
+    fn world_to_screen_letterbox() {
+        let mut camera = KaCamera::new(320.0, 180.0);
+        camera.letterbox = true;
+        camera.set_pos_xy(-100.0, 50.0);
+        //Scaled by 2.5 to 800x450 with bars of 75 pixels.
+        let rect = camera.get_screen_rect(800.0, 600.0);
+        assert_px(Vec2::new(rect.x, rect.y), Vec2::new(0.0, 75.0));
+        assert_px(Vec2::new(rect.w, rect.h), Vec2::new(800.0, 450.0));
+        assert_px(camera.world_to_screen_in(Vec2::new(-100.0, 50.0), rect), Vec2::new(400.0, 300.0));
+        assert_px(camera.world_to_screen_in(Vec2::new(60.0, 140.0), rect), Vec2::new(800.0, 525.0));
+        assert_px(camera.world_to_screen_in(Vec2::new(-260.0, -40.0), rect), Vec2::new(0.0, 75.0));
+
+        //The right half of the screen, 400x600 scaled by 1.25 to 400x225.
+        camera.set_viewport(0.5, 0.0, 0.5, 1.0);
+        let rect = camera.get_screen_rect(800.0, 600.0);
+        assert_px(Vec2::new(rect.x, rect.y), Vec2::new(400.0, 187.0));
+        assert_px(camera.world_to_screen_in(Vec2::new(60.0, 140.0), rect), Vec2::new(800.0, 412.0));
+    }
+
+    #[test]
+    fn letterbox_keeps_aspect() {
+        let mut camera = KaCamera::new(320.0, 180.0);
+        camera.letterbox = true;
+        for viewport in [None, Some(Rect::new(0.5, 0.0, 0.5, 1.0)), Some(Rect::new(0.0, 0.5, 1.0, 0.5))].iter() {
+            camera.viewport = *viewport;
+            for (w, h) in SCREEN_SIZES.iter() {
+                let rect = camera.get_screen_rect(*w, *h);
+                assert!((rect.w / rect.h - 320.0 / 180.0).abs() < 0.001, "{:?} on {}x{}", rect, w, h);
+                let area = match viewport {
+                    Some(v) => Rect::new(v.x * w, v.y * h, v.w * w, v.h * h),
+                    None => Rect::new(0.0, 0.0, *w, *h),
+                };
+                //Fits in the area and touches two of its sides.
+                assert!(rect.w <= area.w + 0.001 && rect.h <= area.h + 0.001);
+                assert!((rect.w - area.w).abs() < 0.001 || (rect.h - area.h).abs() < 0.001);
+                assert!(rect.x >= area.x - 1.0 && rect.y >= area.y - 1.0);
+            }
+        }
+    }
+}
@@ -1,41 +1,30 @@
-pub async fn load_csv_from_file(path: &str) -> Vec<csv::StringRecord> {
-    use macroquad::prelude::load_file;
-    use csv::ReaderBuilder;
-    let file = load_file(path)
-        .await
-        .unwrap_or_else(|e| panic! {"Invalid file : {} {}", path, e});
+use crate::{KaGame, KaError, KaResult, KaAssetKind};
 
-    let csv = match std::str::from_utf8(&file) {
-        Ok(v) => v,
-        Err(e) => panic!("File \"{}\" have Invalid UTF-8 sequence: {} ", path, e),
-    };
+//Looks in the packs mounted in the game before the disk.
+pub async fn load_csv_from_file(game: &mut KaGame, path: &str) -> KaResult<Vec<csv::StringRecord>> {
+    let file = game.get_resources_mut().read_file_watched(path, KaAssetKind::Csv).await?;
 
-    let mut rdr = ReaderBuilder::new().from_reader(csv.as_bytes());
-    let mut records: Vec<csv::StringRecord> = Vec::new();
-    for result in rdr.records() {
-        let mut record = result.expect("a CSV record");
-        record.trim();
-        records.push(record);
-    }
-    records
+    read_csv(path, &file)
     
 }
 
-pub fn load_csv_from_bytes(bytes: &[u8]) -> Vec<csv::StringRecord> {
+pub fn load_csv_from_bytes(bytes: &[u8]) -> KaResult<Vec<csv::StringRecord>> {
+    read_csv("bytes", bytes)
+}
+
+pub(crate) fn read_csv(path: &str, bytes: &[u8]) -> KaResult<Vec<csv::StringRecord>> {
     use csv::ReaderBuilder;
 
-    let csv = match std::str::from_utf8(bytes) {
-        Ok(v) => v,
-        Err(e) => panic!("From Bytes have Invalid UTF-8 sequence: {} ", e),
-    };
+    let csv = std::str::from_utf8(bytes)
+        .map_err(|e| KaError::BadEncoding {path: path.to_owned(), message: e.to_string()})?;
 
     let mut rdr = ReaderBuilder::new().from_reader(csv.as_bytes());
     let mut records: Vec<csv::StringRecord> = Vec::new();
     for result in rdr.records() {
-        let mut record = result.expect("a CSV record");
+        let mut record = result.map_err(|e| KaError::Decode {path: path.to_owned(), message: e.to_string()})?;
         record.trim();
         records.push(record);
     }
-    records
+    Ok(records)
     
 }
\ No newline at end of file
@@ -0,0 +1,65 @@
+//Easing curves for tweens, t goes from 0.0 to 1.0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KaEase {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    SmoothStep,
+}
+
+impl KaEase {
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.max(0.0).min(1.0);
+        match self {
+            KaEase::Linear => t,
+            KaEase::QuadIn => t * t,
+            KaEase::QuadOut => t * (2.0 - t),
+            KaEase::QuadInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    -1.0 + (4.0 - 2.0 * t) * t
+                }
+            },
+            KaEase::SmoothStep => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+//Keeps the time of a transition, get_t returns the eased progress.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KaTween {
+    pub duration: f32,
+    pub timer: f32,
+    pub ease: KaEase,
+}
+
+impl KaTween {
+    pub fn new(duration: f32, ease: KaEase) -> Self {
+        Self {
+            duration,
+            timer: 0.0,
+            ease,
+        }
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        self.timer = (self.timer + delta).min(self.duration);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.timer >= self.duration
+    }
+
+    pub fn get_t(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+        self.ease.apply(self.timer / self.duration)
+    }
+
+    pub fn lerp(&self, from: f32, to: f32) -> f32 {
+        from + (to - from) * self.get_t()
+    }
+}
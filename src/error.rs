@@ -0,0 +1,30 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KaError {
+    //The file could not be read.
+    MissingAsset {path: String, message: String},
+    //The data is not a valid texture, sound, csv...
+    Decode {path: String, message: String},
+    //Text files must be UTF-8.
+    BadEncoding {path: String, message: String},
+    //Nothing was loaded with that name.
+    UnknownName(String),
+    Shader {name: String, message: String},
+}
+
+impl fmt::Display for KaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KaError::MissingAsset {path, message} => write!(f, "Missing asset \"{}\": {}", path, message),
+            KaError::Decode {path, message} => write!(f, "Could not decode \"{}\": {}", path, message),
+            KaError::BadEncoding {path, message} => write!(f, "\"{}\" have Invalid UTF-8 sequence: {}", path, message),
+            KaError::UnknownName(name) => write!(f, "Unknown name \"{}\"", name),
+            KaError::Shader {name, message} => write!(f, "Could not load shader \"{}\": {}", name, message),
+        }
+    }
+}
+
+impl std::error::Error for KaError {}
+
+pub type KaResult<T> = Result<T, KaError>;
@@ -1,11 +1,46 @@
 use std::ops::Range;
 use std::collections::HashMap;
+use std::sync::Arc;
 use macroquad::prelude::{Vec2, Texture2D, Rect};
 use hecs::{World, Entity};
 use crate::KaGame;
 use crate::KaSprite;
 
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KaPlayMode {
+    Forward,
+    Reverse,
+    PingPong,
+}
+
+//An animation event, it can capture data or carry a typed payload.
+#[derive(Clone)]
+pub struct KaFrameEvent {
+    callback: Arc<dyn Fn(&mut World, &mut KaGame, Entity) + Send + Sync>,
+}
+
+impl KaFrameEvent {
+    pub fn new<F>(callback: F) -> Self
+        where F: Fn(&mut World, &mut KaGame, Entity) + Send + Sync + 'static
+    {
+        Self {
+            callback: Arc::new(callback),
+        }
+    }
+
+    pub fn from_fn(function: fn(&mut World, &mut KaGame, Entity)) -> Self {
+        Self::new(function)
+    }
+
+    pub fn with_payload<T: Send + Sync + 'static>(payload: T, function: fn(&mut World, &mut KaGame, Entity, &T)) -> Self {
+        Self::new(move |world, game, entity| function(world, game, entity, &payload))
+    }
+
+    pub fn call(&self, world: &mut World, game: &mut KaGame, entity: Entity) {
+        (self.callback)(world, game, entity);
+    }
+}
 
 pub struct KaFrames {
     pub range: Range<usize>,
@@ -16,7 +51,26 @@ pub struct KaFrames {
     pub current_frame: usize,
     pub replay: bool,
     pub end_function: fn(&mut World, &mut KaGame, Entity),
-    pub frame_functions: HashMap<usize, fn(&mut World, &mut KaGame, Entity)>
+    pub frame_functions: HashMap<usize, fn(&mut World, &mut KaGame, Entity)>,
+    //Per frame durations, frames not in here use frame_time.
+    pub frame_times: HashMap<usize, f32>,
+    pub mode: KaPlayMode,
+    //Multiplies the delta, 2.0 plays twice as fast.
+    pub speed: f32,
+    pub paused: bool,
+    pub finished: bool,
+    //Used by PingPong to know if we are going back.
+    pub backwards: bool,
+    //Named ranges, played with play_clip.
+    pub clips: HashMap<String, Range<usize>>,
+    pub current_clip: Option<String>,
+    //Events by absolute frame number.
+    pub frame_events: HashMap<usize, Vec<KaFrameEvent>>,
+    //Events by clip name and frame index inside the clip (0 is the first frame of the clip).
+    pub clip_events: HashMap<String, HashMap<usize, Vec<KaFrameEvent>>>,
+    pub clip_end_events: HashMap<String, Vec<KaFrameEvent>>,
+    //The first frame of a range has not been reported yet, set by restart.
+    pub pending_enter: bool,
 }
 
 impl KaFrames {
@@ -33,6 +87,18 @@ impl KaFrames {
             replay,
             end_function,
             frame_functions: HashMap::new(),
+            frame_times: HashMap::new(),
+            mode: KaPlayMode::Forward,
+            speed: 1.0,
+            paused: false,
+            finished: false,
+            backwards: false,
+            clips: HashMap::new(),
+            current_clip: None,
+            frame_events: HashMap::new(),
+            clip_events: HashMap::new(),
+            clip_end_events: HashMap::new(),
+            pending_enter: true,
         }
     }
 
@@ -54,15 +120,217 @@ impl KaFrames {
 
     pub fn set_range(&mut self, range: Range<usize>) {
         if self.range != range {
-            self.current_frame = range.start;
             self.range = range;
-            
-            self.timer = 0.0;
+            self.current_clip = None;
+            self.restart();
         }
         self.reset_end_fn();
         
     }
 
+    pub fn add_clip(&mut self, name: &str, range: Range<usize>) {
+        self.clips.insert(name.to_owned(), range);
+    }
+
+    //Returns false if there is no clip with that name.
+    pub fn play_clip(&mut self, name: &str) -> bool {
+        match self.clips.get(name) {
+            Some(range) => {
+                let range = range.clone();
+                //Two clips can share a range, the new one still starts from its first frame.
+                if self.range == range && !self.is_playing_clip(name) {
+                    self.restart();
+                }
+                self.set_range(range);
+                self.current_clip = Some(name.to_owned());
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn is_playing_clip(&self, name: &str) -> bool {
+        self.current_clip.as_deref() == Some(name)
+    }
+
+    pub fn add_frame_event(&mut self, frame_number: usize, event: KaFrameEvent) {
+        self.frame_events.entry(frame_number).or_insert_with(Vec::new).push(event);
+    }
+
+    pub fn add_clip_event(&mut self, clip: &str, frame_index: usize, event: KaFrameEvent) {
+        self.clip_events
+            .entry(clip.to_owned())
+            .or_insert_with(HashMap::new)
+            .entry(frame_index)
+            .or_insert_with(Vec::new)
+            .push(event);
+    }
+
+    pub fn add_clip_end_event(&mut self, clip: &str, event: KaFrameEvent) {
+        self.clip_end_events.entry(clip.to_owned()).or_insert_with(Vec::new).push(event);
+    }
+
+    //Collects the events of a frame that has just been entered.
+    fn collect_frame_events(&self, frame_number: usize, entity: Entity, events: &mut Vec<(KaFrameEvent, Entity)>) {
+        if let Some(function) = self.frame_functions.get(&frame_number) {
+            events.push((KaFrameEvent::from_fn(*function), entity));
+        }
+        if let Some(frame_events) = self.frame_events.get(&frame_number) {
+            for event in frame_events.iter() {
+                events.push((event.clone(), entity));
+            }
+        }
+        if let Some(clip) = &self.current_clip {
+            if frame_number < self.range.start {
+                return;
+            }
+            if let Some(frame_events) = self.clip_events.get(clip).and_then(|c| c.get(&(frame_number - self.range.start))) {
+                for event in frame_events.iter() {
+                    events.push((event.clone(), entity));
+                }
+            }
+        }
+    }
+
+    fn collect_end_events(&self, entity: Entity, events: &mut Vec<(KaFrameEvent, Entity)>) {
+        events.push((KaFrameEvent::from_fn(self.end_function), entity));
+        if let Some(clip) = &self.current_clip {
+            if let Some(end_events) = self.clip_end_events.get(clip) {
+                for event in end_events.iter() {
+                    events.push((event.clone(), entity));
+                }
+            }
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.backwards = self.mode == KaPlayMode::Reverse;
+        self.current_frame = if self.backwards {self.range.end} else {self.range.start};
+        self.timer = 0.0;
+        self.finished = false;
+        self.pending_enter = true;
+    }
+
+    pub fn set_mode(&mut self, mode: KaPlayMode) {
+        if self.mode != mode {
+            self.mode = mode;
+            self.restart();
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn set_frame_time(&mut self, frame_number: usize, time: f32) {
+        self.frame_times.insert(frame_number, time);
+    }
+
+    //Sets the durations of consecutive frames starting from first_frame.
+    pub fn set_frame_times(&mut self, first_frame: usize, times: &[f32]) {
+        for (i, time) in times.iter().enumerate() {
+            self.frame_times.insert(first_frame + i, *time);
+        }
+    }
+
+    pub fn clear_frame_times(&mut self) {
+        self.frame_times.clear();
+    }
+
+    pub fn get_frame_time(&self, frame_number: usize) -> f32 {
+        match self.frame_times.get(&frame_number) {
+            Some(time) => *time,
+            None => self.frame_time,
+        }
+    }
+
+    //Moves to the next frame of the range (range.end is the last frame played).
+    //Returns true when the animation reached its end, either looping or finishing.
+    pub fn next_frame(&mut self) -> bool {
+        let (start, end) = (self.range.start, self.range.end);
+        if !self.backwards {
+            if self.current_frame < end {
+                self.current_frame += 1;
+                return false;
+            }
+            if self.mode == KaPlayMode::PingPong && end > start {
+                self.backwards = true;
+                self.current_frame -= 1;
+                return false;
+            }
+        } else if self.current_frame > start {
+            self.current_frame -= 1;
+            return false;
+        }
+
+        if !self.replay {
+            self.finished = true;
+            return true;
+        }
+        match self.mode {
+            KaPlayMode::Forward => self.current_frame = start,
+            KaPlayMode::Reverse => self.current_frame = end,
+            KaPlayMode::PingPong => {
+                self.backwards = false;
+                self.current_frame = if end > start {start + 1} else {start};
+            }
+        }
+        true
+    }
+
+    //Advances the timer and returns the frames entered and how many times the end was reached.
+    //A big delta can skip several frames, all of them are reported.
+    //The starting frame is reported on the first advance after new, restart or a new clip.
+    pub fn advance(&mut self, delta: f32, entered: &mut Vec<usize>) -> usize {
+        let mut ends = 0;
+        if self.pending_enter {
+            self.pending_enter = false;
+            entered.push(self.current_frame);
+        }
+        if self.paused || self.finished {
+            return ends;
+        }
+        self.timer += delta * self.speed;
+        loop {
+            let frame_time = self.get_frame_time(self.current_frame);
+            if self.timer < frame_time {
+                break;
+            }
+            self.timer -= frame_time;
+            let ended = self.next_frame();
+            if ended {
+                ends += 1;
+            }
+            if self.finished {
+                self.timer = 0.0;
+                break;
+            }
+            entered.push(self.current_frame);
+            //A zero frame time would never consume the timer.
+            if frame_time <= 0.0 {
+                self.timer = 0.0;
+                break;
+            }
+        }
+        ends
+    }
+
     pub fn set_range_and_end_fn(&mut self, range: Range<usize>, end_fn: fn(&mut World, &mut KaGame, Entity)) {
         self.set_range(range);
         self.set_end_fn(end_fn);
@@ -79,33 +347,24 @@ pub fn end_frame_null(_world: &mut World, _game: &mut KaGame, _entity: Entity) {
 }
 
 pub fn ka_sprite_frames(world: &mut World, game: &mut KaGame, delta: f32) {
-    let mut fn_to_call: Vec<(fn(&mut World, &mut KaGame, Entity), Entity)> = Vec::new();
+    let mut fn_to_call: Vec<(KaFrameEvent, Entity)> = Vec::new();
+    let mut entered: Vec<usize> = Vec::new();
     for (e, (mut sprite, mut frames)) in world.query::<(&mut KaSprite, &mut KaFrames)>().iter() {
-        frames.timer += delta;
-        if frames.timer >= frames.frame_time {
-            frames.timer -= frames.frame_time;
-            frames.current_frame += 1;
+        entered.clear();
+        let ends = frames.advance(delta, &mut entered);
+        for frame in entered.iter() {
             //One game frame damage.
-            if let Some(result) = frames.frame_functions.get(&frames.current_frame) {
-                fn_to_call.push((*result, e.clone()));
-            }
-            
-            
-            if frames.current_frame > frames.range.end {
-                if frames.replay{
-                    frames.current_frame = frames.range.start;
-                } else {
-                    frames.current_frame = frames.range.end;
-                }
-                fn_to_call.push((frames.end_function, e.clone()));
-            }
+            frames.collect_frame_events(*frame, e.clone(), &mut fn_to_call);
+        }
+        for _ in 0..ends {
+            frames.collect_end_events(e.clone(), &mut fn_to_call);
         }
 
         sprite.frame = frames.get_current_frame_rect();
     }
     
-    for (func, e) in fn_to_call.iter() {
-        (func)(world, game, e.clone());
+    for (event, e) in fn_to_call.iter() {
+        event.call(world, game, e.clone());
     }
     
 }
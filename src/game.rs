@@ -1,12 +1,13 @@
-use macroquad::prelude::{Texture2D, FilterMode};
+use macroquad::prelude::{Texture2D, Font, FilterMode, Vec2, RenderTarget};
 use hecs::{World, EntityBuilder, Entity};
 use quad_snd::{mixer::SoundMixer};
-use crate::{Resources, Spawner, Despawner, Actions, ActionInput, ka_aabb_update};
+use crate::shaders::KaShaderDescriptor;
+use crate::{Resources, Spawner, Despawner, Actions, ActionInput, ka_aabb_update, KaCamera, KaPostProcess, KaPostEffect, KaResult, KaError, Handle, KaPack, KaMaterial, KaAudio, KaPlayOptions, KaSoundHandle};
 
-use quad_snd::{mixer::SoundId};
+use quad_snd::{mixer::Sound};
 
-#[derive(Debug, Clone)]
-pub struct NoSoundError;
+#[cfg(feature = "hot-reload")]
+use crate::KaHotReload;
 
 
 pub struct KaGame {
@@ -15,6 +16,10 @@ pub struct KaGame {
     despawner: Despawner,
     actions: Actions,
     pub mixer: SoundMixer,
+    pub audio: KaAudio,
+    pub post_process: Option<KaPostProcess>,
+    #[cfg(feature = "hot-reload")]
+    pub hot_reload: KaHotReload,
 
 }
 
@@ -26,6 +31,10 @@ impl KaGame {
             despawner: Despawner::new(),
             actions: Actions::new(),
             mixer: SoundMixer::new(),
+            audio: KaAudio::new(),
+            post_process: None,
+            #[cfg(feature = "hot-reload")]
+            hot_reload: KaHotReload::new(),
         }
     }
 
@@ -41,15 +50,50 @@ impl KaGame {
         self.resources.set_texture_filter_mode(filter);
     }
 
-    pub async fn load_texture(&mut self, path: &str) -> Texture2D {
+    pub async fn load_texture(&mut self, path: &str) -> KaResult<Handle<Texture2D>> {
         self.resources.load_texture(path).await
     }
 
-    /*
-    pub fn load_texture_from_bytes(&mut self, name: &str, bytes: &[u8]) -> Texture2D {
+    //For the assets embedded with include_bytes!.
+    pub fn load_texture_from_bytes(&mut self, name: &str, bytes: &[u8]) -> KaResult<Handle<Texture2D>> {
         self.resources.load_texture_from_bytes(name, bytes)
     }
-    */
+
+    pub fn load_csv_from_bytes(&mut self, name: &str, bytes: &[u8]) -> KaResult<()> {
+        self.resources.load_csv_from_bytes(name, bytes)
+    }
+
+    pub fn load_font_from_bytes(&mut self, name: &str, bytes: &[u8]) -> KaResult<()> {
+        self.resources.load_font_from_bytes(name, bytes)
+    }
+
+    pub fn load_shader_from_bytes(&mut self, name: &str, bytes: &[u8]) -> KaResult<()> {
+        self.resources.load_shader_from_bytes(name, bytes)
+    }
+
+    //load_texture, read_file, load_csv_from_file, KaAssetManifest::load_from_file
+    //and KaAssetLoader look in the mounted packs before the disk.
+    pub fn mount(&mut self, pack: KaPack) {
+        self.resources.mount(pack);
+    }
+
+    pub async fn mount_from_file(&mut self, path: &str) -> KaResult<()> {
+        let pack = KaPack::load_from_file(path).await?;
+        self.resources.mount(pack);
+        Ok(())
+    }
+
+    pub fn unmount(&mut self, name: &str) -> Option<KaPack> {
+        self.resources.unmount(name)
+    }
+
+    pub fn get_packed(&self, path: &str) -> Option<&[u8]> {
+        self.resources.get_packed(path)
+    }
+
+    pub async fn read_file(&self, path: &str) -> KaResult<Vec<u8>> {
+        self.resources.read_file(path).await
+    }
     
     
 
@@ -57,21 +101,184 @@ impl KaGame {
         self.resources.get_texture(path)
     }
 
+    pub fn get_texture_handle(&self, path: &str) -> Option<Handle<Texture2D>> {
+        self.resources.get_texture_handle(path)
+    }
+
+    //Texture2D is a copy of the gl texture id, None after the texture is unloaded.
+    pub fn get_texture_from_handle(&self, handle: Handle<Texture2D>) -> Option<Texture2D> {
+        self.resources.get_texture_from_handle(handle).copied()
+    }
+
+    pub fn add_texture_ref(&mut self, handle: Handle<Texture2D>) {
+        self.resources.add_texture_ref(handle);
+    }
+
+    pub fn release_texture(&mut self, handle: Handle<Texture2D>) {
+        self.resources.release_texture(handle);
+    }
+
+    pub fn unload_unused_textures(&mut self) {
+        self.resources.unload_unused_textures();
+    }
+
+    pub fn get_csv(&self, name: &str) -> Option<&Vec<csv::StringRecord>> {
+        self.resources.get_csv(name)
+    }
+
+    pub fn get_font(&self, name: &str) -> Option<&Font> {
+        self.resources.get_font(name)
+    }
+
+    pub fn get_shader(&self, name: &str) -> Option<&str> {
+        self.resources.get_shader(name)
+    }
+
+    //With the hot-reload feature a <shader_dir>/<name>.glsl file overrides the default source.
+    #[allow(unused_variables)]
+    pub fn get_shader_source(&mut self, name: &str, default: &str) -> String {
+        #[cfg(feature = "hot-reload")]
+        return self.hot_reload.get_shader_source(name, default);
+        #[cfg(not(feature = "hot-reload"))]
+        default.to_owned()
+    }
+
+    //A sprite material from the descriptor, the fragment source goes through get_shader_source.
+    pub fn load_shader_material(&mut self, descriptor: &KaShaderDescriptor) -> KaResult<KaMaterial> {
+        let fragment = self.get_shader_source(descriptor.name, descriptor.fragment);
+        descriptor.load_material_with(&fragment)
+    }
+
+    //A post effect from the descriptor, the fragment source goes through get_shader_source.
+    //With the hot reload the effects in the post process are rebuilt when their file changes.
+    pub fn load_post_effect(&mut self, descriptor: &KaShaderDescriptor) -> KaResult<KaPostEffect> {
+        let fragment = self.get_shader_source(descriptor.name, descriptor.fragment);
+        KaPostEffect::from_descriptor_with(descriptor, &fragment)
+    }
+
+    pub(crate) fn get_resources_mut(&mut self) -> &mut Resources {
+        &mut self.resources
+    }
+
     
-    pub fn load_ogg(&mut self, name: &str, data: &[u8], looped: bool) {
-        self.resources.load_ogg(name, data, looped);
+    pub fn load_sound(&mut self, name: &str, data: &[u8], looped: bool) -> KaResult<Handle<Sound>> {
+        self.resources.load_sound(name, data, looped)
     }
 
-    pub fn play_sound(&mut self, name: &str) -> Result<SoundId, NoSoundError>{
-        match self.resources.get_sound(name) {
-            Some(sound) => {
-                Ok(self.mixer.play(sound.clone()))
-            },
-            None => Err(NoSoundError),
-        }
+    pub fn load_ogg(&mut self, name: &str, data: &[u8], looped: bool) -> KaResult<Handle<Sound>> {
+        self.resources.load_ogg(name, data, looped)
+    }
+
+    pub fn get_sound_handle(&self, name: &str) -> Option<Handle<Sound>> {
+        self.resources.get_sound_handle(name)
+    }
+
+    //On the sfx bus.
+    pub fn play_sound(&mut self, name: &str) -> KaResult<KaSoundHandle> {
+        self.play_sound_with(name, &KaPlayOptions::default())
+    }
+
+    pub fn play_sound_with(&mut self, name: &str, options: &KaPlayOptions) -> KaResult<KaSoundHandle> {
+        let sound = self.resources.try_get_sound(name)?;
+        Ok(self.audio.play(&mut self.mixer, sound, options))
+    }
+
+    pub fn play_sound_handle(&mut self, handle: Handle<Sound>) -> KaResult<KaSoundHandle> {
+        self.play_sound_handle_with(handle, &KaPlayOptions::default())
+    }
+
+    pub fn play_sound_handle_with(&mut self, handle: Handle<Sound>, options: &KaPlayOptions) -> KaResult<KaSoundHandle> {
+        let sound = self.resources.get_sound_from_handle(handle)
+            .ok_or_else(|| KaError::UnknownName(format!("{:?}", handle)))?;
+        Ok(self.audio.play(&mut self.mixer, sound, options))
+    }
+
+    pub fn stop_sound(&mut self, handle: KaSoundHandle) {
+        self.audio.stop(&mut self.mixer, handle);
+    }
+
+    pub fn fade_out_sound(&mut self, handle: KaSoundHandle, duration: f32) {
+        self.audio.fade_out(handle, duration);
+    }
+
+    //A sound that does not move, heard from the camera when it starts.
+    //Uses the spatial settings of game.audio, see KaSoundEmitter for moving sounds.
+    pub fn play_sound_at(&mut self, name: &str, pos: Vec2, camera: &KaCamera) -> KaResult<KaSoundHandle> {
+        let (volume, pan) = self.audio.spatial.get_volume_pan(pos, camera.get_pos());
+        self.play_sound_with(name, &KaPlayOptions {
+            volume,
+            pan: Some(pan),
+            ..Default::default()
+        })
+    }
+
+    //Crossfades from the current music, in seconds.
+    pub fn play_music(&mut self, name: &str, crossfade: f32) -> KaResult<()> {
+        let sound = self.resources.try_get_sound(name)?;
+        self.audio.play_music(&mut self.mixer, name, sound, crossfade);
+        Ok(())
+    }
+
+    pub fn stop_music(&mut self, fade: f32) {
+        self.audio.stop_music(&mut self.mixer, fade);
+    }
+
+    //Runs the fades of the sounds and applies the bus volumes, call it every frame.
+    pub fn update_audio(&mut self, delta: f32) {
+        self.audio.update(&mut self.mixer, delta);
+    }
+
+    pub fn add_sound_ref(&mut self, handle: Handle<Sound>) {
+        self.resources.add_sound_ref(handle);
+    }
+
+    pub fn release_sound(&mut self, handle: Handle<Sound>) {
+        self.resources.release_sound(handle);
+    }
+
+    pub fn unload_unused_sounds(&mut self) {
+        self.resources.unload_unused_sounds();
     }
     
 
+    pub fn enable_post_process(&mut self, width: u32, height: u32) {
+        self.post_process = Some(KaPostProcess::new(width, height));
+    }
+
+    pub fn disable_post_process(&mut self) {
+        self.post_process = None;
+    }
+
+    //Enables the post process with the screen size if needed.
+    pub fn add_post_effect(&mut self, effect: KaPostEffect) {
+        use macroquad::prelude::{screen_width, screen_height};
+        if self.post_process.is_none() {
+            self.enable_post_process(screen_width() as u32, screen_height() as u32);
+        }
+        if let Some(post_process) = &mut self.post_process {
+            post_process.add_effect(effect);
+        }
+    }
+
+    //Call it before drawing the scene, without post process it just sets the camera.
+    pub fn begin_post_process(&self, camera: &KaCamera) {
+        match &self.post_process {
+            Some(post_process) => post_process.begin(camera),
+            None => camera.set(),
+        }
+    }
+
+    //The render target of the post process, None when the scene is drawn to the screen.
+    pub fn get_draw_target(&self) -> Option<RenderTarget> {
+        self.post_process.as_ref().map(|post_process| post_process.get_target())
+    }
+
+    pub fn end_post_process(&mut self, delta: f32) {
+        if let Some(post_process) = &mut self.post_process {
+            post_process.end(delta);
+        }
+    }
+
     pub fn to_spawn(&mut self, builder: EntityBuilder) {
         self.spawner.add(builder);
     }
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+//A cheap reference to an asset in KaAssets. A handle of a removed asset never points to a new one.
+pub struct Handle<T> {
+    index: u32,
+    generation: u32,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    fn new(index: u32, generation: u32) -> Self {
+        Self {
+            index,
+            generation,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Handle({}v{})", self.index, self.generation)
+    }
+}
+
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+    name: String,
+    ref_count: u32,
+}
+
+//Assets of one type. Every asset starts with one reference, unload_unused removes the ones with zero.
+//The components keep handles, not the assets, so they never use a removed asset.
+pub struct KaAssets<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    names: HashMap<String, Handle<T>>,
+}
+
+impl<T> KaAssets<T> {
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    //Loading again with the same name replaces the asset and keeps the handle,
+    //the replaced asset is returned so it can be destroyed.
+    pub fn insert(&mut self, name: &str, value: T) -> (Handle<T>, Option<T>) {
+        if let Some(handle) = self.names.get(name).copied() {
+            let slot = &mut self.slots[handle.index as usize];
+            return (handle, slot.value.replace(value));
+        }
+        let index = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.value = Some(value);
+                slot.name = name.to_owned();
+                slot.ref_count = 1;
+                index
+            },
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: Some(value),
+                    name: name.to_owned(),
+                    ref_count: 1,
+                });
+                self.slots.len() as u32 - 1
+            }
+        };
+        let handle = Handle::new(index, self.slots[index as usize].generation);
+        self.names.insert(name.to_owned(), handle);
+        (handle, None)
+    }
+
+    fn get_slot(&self, handle: Handle<T>) -> Option<&Slot<T>> {
+        match self.slots.get(handle.index as usize) {
+            Some(slot) if slot.generation == handle.generation && slot.value.is_some() => Some(slot),
+            _ => None,
+        }
+    }
+
+    fn get_slot_mut(&mut self, handle: Handle<T>) -> Option<&mut Slot<T>> {
+        match self.slots.get_mut(handle.index as usize) {
+            Some(slot) if slot.generation == handle.generation && slot.value.is_some() => Some(slot),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, handle: Handle<T>) -> Option<&T> {
+        self.get_slot(handle).and_then(|slot| slot.value.as_ref())
+    }
+
+    pub fn get_mut(&mut self, handle: Handle<T>) -> Option<&mut T> {
+        self.get_slot_mut(handle).and_then(|slot| slot.value.as_mut())
+    }
+
+    pub fn get_handle(&self, name: &str) -> Option<Handle<T>> {
+        self.names.get(name).copied()
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<&T> {
+        self.get_handle(name).and_then(move |handle| self.get(handle))
+    }
+
+    pub fn get_name(&self, handle: Handle<T>) -> Option<&str> {
+        self.get_slot(handle).map(|slot| slot.name.as_str())
+    }
+
+    pub fn contains(&self, handle: Handle<T>) -> bool {
+        self.get_slot(handle).is_some()
+    }
+
+    pub fn add_ref(&mut self, handle: Handle<T>) {
+        if let Some(slot) = self.get_slot_mut(handle) {
+            slot.ref_count += 1;
+        }
+    }
+
+    pub fn release(&mut self, handle: Handle<T>) {
+        if let Some(slot) = self.get_slot_mut(handle) {
+            slot.ref_count = slot.ref_count.saturating_sub(1);
+        }
+    }
+
+    pub fn get_ref_count(&self, handle: Handle<T>) -> u32 {
+        self.get_slot(handle).map(|slot| slot.ref_count).unwrap_or(0)
+    }
+
+    pub fn remove(&mut self, handle: Handle<T>) -> Option<T> {
+        let slot = self.get_slot_mut(handle)?;
+        let value = slot.value.take();
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.ref_count = 0;
+        let name = std::mem::replace(&mut slot.name, String::new());
+        self.names.remove(&name);
+        self.free.push(handle.index);
+        value
+    }
+
+    //Removes the assets without references and returns them so they can be destroyed.
+    pub fn unload_unused(&mut self) -> Vec<T> {
+        let unused: Vec<Handle<T>> = self.slots.iter().enumerate()
+            .filter(|(_, slot)| slot.value.is_some() && slot.ref_count == 0)
+            .map(|(i, slot)| Handle::new(i as u32, slot.generation))
+            .collect();
+        unused.into_iter().filter_map(|handle| self.remove(handle)).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+use macroquad::prelude::Texture2D;
+use hecs::World;
+
+use crate::{KaGame, KaAsset, KaAssetKind, KaPostEffect, KaError, KaResult};
+use crate::resources::{decode_image, decode_font};
+use crate::shaders::get_effect;
+use crate::csv_loader::read_csv;
+
+fn get_modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+//Polls the files loaded through Resources and csv_loader and reloads the ones that changed.
+//Only for development, it reads the files from disk.
+pub struct KaHotReload {
+    pub enabled: bool,
+    //Seconds between checks.
+    pub interval: f32,
+    //Folder with <name>.glsl files that override the shaders of shaders.rs.
+    pub shader_dir: Option<String>,
+    timer: f32,
+    files: HashMap<String, (Vec<KaAsset>, Option<SystemTime>)>,
+    csv_consumers: Vec<(String, fn(&mut World, &mut KaGame, &[csv::StringRecord]))>,
+    changed_shaders: Vec<String>,
+}
+
+impl KaHotReload {
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            interval: 0.5,
+            shader_dir: None,
+            timer: 0.0,
+            files: HashMap::new(),
+            csv_consumers: Vec::new(),
+            changed_shaders: Vec::new(),
+        }
+    }
+
+    pub fn watch(&mut self, asset: KaAsset) {
+        let modified = get_modified(&asset.path);
+        let entry = self.files.entry(asset.path.clone()).or_insert_with(|| (Vec::new(), modified));
+        if !entry.0.contains(&asset) {
+            entry.0.push(asset);
+        }
+    }
+
+    //consumer is called with the new records every time the csv file changes.
+    pub fn add_csv_consumer(&mut self, path: &str, consumer: fn(&mut World, &mut KaGame, &[csv::StringRecord])) {
+        self.watch(KaAsset {
+            name: path.to_owned(),
+            path: path.to_owned(),
+            kind: KaAssetKind::Csv,
+        });
+        self.csv_consumers.push((path.to_owned(), consumer));
+    }
+
+    //Names of the shaders reloaded since the last call, the materials that use them must be rebuilt.
+    pub fn take_changed_shaders(&mut self) -> Vec<String> {
+        std::mem::replace(&mut self.changed_shaders, Vec::new())
+    }
+
+    //The source of <shader_dir>/<name>.glsl or the default. The file is watched.
+    pub fn get_shader_source(&mut self, name: &str, default: &str) -> String {
+        if let Some(dir) = &self.shader_dir {
+            let path = format!("{}/{}.glsl", dir, name);
+            if let Ok(source) = std::fs::read_to_string(&path) {
+                self.watch(KaAsset {
+                    name: name.to_owned(),
+                    path,
+                    kind: KaAssetKind::Shader,
+                });
+                return source;
+            }
+        }
+        default.to_owned()
+    }
+
+    //Returns the paths that changed since the last check.
+    fn get_changed(&mut self) -> Vec<String> {
+        let mut changed: Vec<String> = Vec::new();
+        for (path, (_, modified)) in self.files.iter_mut() {
+            let current = get_modified(path);
+            if current.is_some() && current != *modified {
+                *modified = current;
+                changed.push(path.clone());
+            }
+        }
+        changed
+    }
+}
+
+//A file saved halfway does not decode, the old texture is kept until the next save.
+fn reload_texture(game: &mut KaGame, name: &str, path: &str, bytes: &[u8]) -> KaResult<()> {
+    let image = decode_image(path, bytes)?;
+    let old = game.get_texture(name).copied();
+    match old {
+        Some(old) if old.width() as u16 == image.width && old.height() as u16 == image.height => {
+            //Same size, the sprites keep the same texture.
+            old.update(&image);
+        },
+        _ => {
+            let texture = Texture2D::from_image(&image);
+            //The sprites keep the handle and draw the new texture.
+            if let Some(old) = game.get_resources_mut().replace_texture(name, texture) {
+                old.delete();
+            }
+        }
+    }
+    Ok(())
+}
+
+//Rebuilds the post effect with the same name, the old one is kept if the shader does not compile.
+fn reload_post_effect(game: &mut KaGame, name: &str, source: &str) -> KaResult<()> {
+    let descriptor = match get_effect(name) {
+        Some(descriptor) => descriptor,
+        None => return Ok(()),
+    };
+    if let Some(post_process) = &mut game.post_process {
+        if let Some(effect) = post_process.get_effect_mut(name) {
+            let mut new_effect = KaPostEffect::from_descriptor_with(descriptor, source)?;
+            new_effect.enabled = effect.enabled;
+            *effect = new_effect;
+        }
+    }
+    Ok(())
+}
+
+fn reload_asset(world: &mut World, game: &mut KaGame, asset: &KaAsset, bytes: &[u8]) -> KaResult<()> {
+    match asset.kind {
+        KaAssetKind::Texture => reload_texture(game, &asset.name, &asset.path, bytes)?,
+        KaAssetKind::Sound {looped} => {
+            game.get_resources_mut().load_sound(&asset.name, bytes, looped)?;
+        },
+        KaAssetKind::Csv => {
+            let records = read_csv(&asset.path, bytes)?;
+            let resources = game.get_resources_mut();
+            if resources.get_csv(&asset.name).is_some() {
+                resources.insert_csv(&asset.name, records.clone());
+            }
+            let consumers: Vec<fn(&mut World, &mut KaGame, &[csv::StringRecord])> = game.hot_reload.csv_consumers.iter()
+                .filter(|(path, _)| *path == asset.path)
+                .map(|(_, consumer)| *consumer)
+                .collect();
+            for consumer in consumers.iter() {
+                consumer(world, game, &records);
+            }
+        },
+        KaAssetKind::Font => {
+            let font = decode_font(&asset.path, bytes)?;
+            game.get_resources_mut().insert_font(&asset.name, font);
+        },
+        KaAssetKind::Shader => {
+            let source = String::from_utf8(bytes.to_vec())
+                .map_err(|e| KaError::BadEncoding {path: asset.path.clone(), message: e.to_string()})?;
+            reload_post_effect(game, &asset.name, &source)?;
+            game.get_resources_mut().insert_shader(&asset.name, source);
+            game.hot_reload.changed_shaders.push(asset.name.clone());
+        },
+    }
+    Ok(())
+}
+
+//Checks the watched files every interval seconds and reloads the changed ones.
+//Returns the errors of the files that could not be reloaded.
+pub fn ka_hot_reload(world: &mut World, game: &mut KaGame, delta: f32) -> Vec<KaError> {
+    let mut errors: Vec<KaError> = Vec::new();
+    if !game.hot_reload.enabled {
+        return errors;
+    }
+    for asset in game.get_resources_mut().take_watched() {
+        game.hot_reload.watch(asset);
+    }
+
+    game.hot_reload.timer += delta;
+    if game.hot_reload.timer < game.hot_reload.interval {
+        return errors;
+    }
+    game.hot_reload.timer = 0.0;
+
+    for path in game.hot_reload.get_changed() {
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                errors.push(KaError::MissingAsset {path, message: e.to_string()});
+                continue;
+            }
+        };
+        let assets = match game.hot_reload.files.get(&path) {
+            Some((assets, _)) => assets.clone(),
+            None => continue,
+        };
+        for asset in assets.iter() {
+            if let Err(e) = reload_asset(world, game, asset, &bytes) {
+                errors.push(e);
+            }
+        }
+    }
+    errors
+}
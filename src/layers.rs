@@ -0,0 +1,132 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use macroquad::prelude::{Vec2, Rect, RenderTarget, set_camera, set_default_camera};
+use hecs::{World, Entity};
+
+use crate::{KaGame, KaSprite, KaSpawnOrder, KaCamera, ka_draw_sprite_list, ka_render_target_camera};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KaSortMode {
+    Z,
+    Y,
+    //Spawn order from KaSpawnOrder, the entities spawned without KaGame::to_spawn
+    //are drawn after the others by entity id.
+    Insertion,
+}
+
+//Added to the entities spawned with KaGame::to_spawn, counts up from 0 in the order of the calls.
+//The ids of hecs are reused after a despawn so they are not the spawn order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct KaSpawnOrder(pub u64);
+
+pub struct KaRenderLayer {
+    pub name: String,
+    pub visible: bool,
+    pub sort_mode: KaSortMode,
+    //false draws the layer in screen space (for the ui).
+    pub use_camera: bool,
+    //How much the layer follows the camera, (1, 1) is the world.
+    pub parallax: Vec2,
+}
+
+impl KaRenderLayer {
+    pub fn new(name: &str, sort_mode: KaSortMode) -> Self {
+        Self {
+            name: name.to_owned(),
+            visible: true,
+            sort_mode,
+            use_camera: true,
+            parallax: Vec2::one(),
+        }
+    }
+}
+
+//Layers are drawn in the order they are added. The layer 0 is "default".
+pub struct KaRenderLayers {
+    pub layers: Vec<KaRenderLayer>,
+    names: HashMap<String, usize>,
+}
+
+impl KaRenderLayers {
+    pub fn new() -> Self {
+        let mut layers = Self {
+            layers: Vec::new(),
+            names: HashMap::new(),
+        };
+        layers.add_layer(KaRenderLayer::new("default", KaSortMode::Z));
+        layers
+    }
+
+    //Returns the index to use in KaSprite.layer.
+    pub fn add_layer(&mut self, layer: KaRenderLayer) -> usize {
+        let index = self.layers.len();
+        self.names.insert(layer.name.clone(), index);
+        self.layers.push(layer);
+        index
+    }
+
+    pub fn get_index(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    pub fn get_layer(&self, name: &str) -> Option<&KaRenderLayer> {
+        self.get_index(name).map(move |i| &self.layers[i])
+    }
+
+    pub fn get_layer_mut(&mut self, name: &str) -> Option<&mut KaRenderLayer> {
+        match self.get_index(name) {
+            Some(i) => Some(&mut self.layers[i]),
+            None => None,
+        }
+    }
+}
+
+type LayerSprite<'a> = (Entity, &'a KaSprite, Option<KaSpawnOrder>);
+
+fn compare(sort_mode: KaSortMode, a: &LayerSprite, b: &LayerSprite) -> Ordering {
+    let order = match sort_mode {
+        KaSortMode::Z => a.1.z.cmp(&b.1.z),
+        KaSortMode::Y => a.1.pos.y().partial_cmp(&b.1.pos.y()).unwrap_or(Ordering::Equal).then(a.1.z.cmp(&b.1.z)),
+        KaSortMode::Insertion => {
+            let spawn_order = |s: &LayerSprite| s.2.map_or(u64::MAX, |order| order.0);
+            return spawn_order(a).cmp(&spawn_order(b)).then(a.0.id().cmp(&b.0.id()));
+        },
+    };
+    order
+        .then(a.1.get_material_id().cmp(&b.1.get_material_id()))
+        .then(a.0.id().cmp(&b.0.id()))
+}
+
+//Draws the sprites layer by layer, sprites with an unknown layer are drawn in the default layer.
+//With a target (KaGame::get_draw_target, KaVirtualScreen.target) the layers are drawn in it instead of the screen.
+//The camera of the world in the same target is set at the end.
+pub fn ka_draw_layers(world: &mut World, game: &KaGame, layers: &KaRenderLayers, camera: &KaCamera, target: Option<RenderTarget>) {
+    let mut q = world.query::<(&KaSprite, Option<&KaSpawnOrder>)>();
+    let mut by_layer: Vec<Vec<LayerSprite>> = layers.layers.iter().map(|_| Vec::new()).collect();
+    for (e, (sprite, spawn_order)) in q.iter() {
+        let layer = if sprite.layer < by_layer.len() {sprite.layer} else {0};
+        by_layer[layer].push((e, sprite, spawn_order.copied()));
+    }
+
+    for (layer, sprites) in layers.layers.iter().zip(by_layer.iter_mut()) {
+        if !layer.visible {
+            continue;
+        }
+        if layer.use_camera {
+            set_camera(camera.get_parallax_camera(layer.parallax, target));
+        } else {
+            match target {
+                Some(target) => {
+                    let rect = Rect::new(0.0, 0.0, target.texture.width(), target.texture.height());
+                    set_camera(ka_render_target_camera(rect, target));
+                },
+                None => set_default_camera(),
+            }
+        }
+        sprites.sort_by(|a, b| compare(layer.sort_mode, a, b));
+        let sprites: Vec<(Entity, &KaSprite)> = sprites.iter().map(|s| (s.0, s.1)).collect();
+        ka_draw_sprite_list(game, &sprites);
+    }
+    set_camera(camera.get_parallax_camera(Vec2::one(), target));
+}
@@ -1,8 +1,14 @@
 mod macros;
 
+mod error;
+pub use error::*;
+
 mod game;
 pub use game::*;
 
+mod handle;
+pub use handle::*;
+
 mod resources;
 use resources::*;
 mod despawner;
@@ -10,15 +16,36 @@ use despawner::*;
 mod spawner;
 use spawner::*;
 
+mod material;
+pub use material::*;
+
 mod sprite;
 pub use sprite::*;
 
+mod layers;
+pub use layers::*;
+
+mod parallax;
+pub use parallax::*;
+
+mod particles;
+pub use particles::*;
+
 mod aabb;
 pub use aabb::*;
 
+mod transform;
+pub use transform::*;
+
+mod ease;
+pub use ease::*;
+
 mod camera;
 pub use camera::*;
 
+mod virtual_screen;
+pub use virtual_screen::*;
+
 pub mod placeholder;
 pub use placeholder::*;
 
@@ -30,10 +57,36 @@ pub use actions::*;
 
 pub mod shaders;
 
+mod post_process;
+pub use post_process::*;
+
+mod lighting;
+pub use lighting::*;
+
 pub mod noise;
 
 pub mod csv_loader;
 pub use csv_loader::*;
 
+mod manifest;
+pub use manifest::*;
+
+mod pack;
+pub use pack::*;
+
+mod audio;
+pub use audio::*;
+
+mod audio_bus;
+pub use audio_bus::*;
+
+mod spatial_audio;
+pub use spatial_audio::*;
+
+#[cfg(feature = "hot-reload")]
+mod hot_reload;
+#[cfg(feature = "hot-reload")]
+pub use hot_reload::*;
+
 
 
@@ -0,0 +1,215 @@
+use macroquad::prelude::{
+    Vec2, Rect, Color, Camera2D, Material, MaterialParams, UniformType, RenderTarget, BLACK, WHITE, DrawTextureParams,
+    load_material, gl_use_material, gl_use_default_material, render_target, set_camera, clear_background,
+    draw_rectangle, draw_triangle, draw_texture_ex,
+};
+use macroquad::miniquad::{PipelineParams, BlendState, BlendFactor, BlendValue, Equation};
+use hecs::World;
+
+use crate::{KaAABB, KaCamera, ka_entity_pos, KaError, KaResult, ka_render_target_camera};
+use crate::shaders::{DEFAULT_VERTEX_SHADER, LIGHT_FRAGMENT_SHADER};
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KaLightKind {
+    Point,
+    //direction and the half angle of the cone in radians.
+    Spot {direction: f32, angle: f32},
+}
+
+//A light at the position of the KaAABB or KaSprite of the entity plus offset.
+//Without any of them offset is the position in the world.
+pub struct KaLight {
+    pub enabled: bool,
+    pub kind: KaLightKind,
+    pub color: Color,
+    pub radius: f32,
+    //Exponent of the falloff, 1.0 is linear.
+    pub falloff: f32,
+    pub intensity: f32,
+    pub offset: Vec2,
+    //Solid KaAABBs in the shadow mask of KaLighting cast shadows.
+    pub shadows: bool,
+}
+
+impl KaLight {
+    pub fn point(color: Color, radius: f32) -> Self {
+        Self {
+            enabled: true,
+            kind: KaLightKind::Point,
+            color,
+            radius,
+            falloff: 1.0,
+            intensity: 1.0,
+            offset: Vec2::zero(),
+            shadows: false,
+        }
+    }
+
+    pub fn spot(color: Color, radius: f32, direction: f32, angle: f32) -> Self {
+        Self {
+            kind: KaLightKind::Spot {direction, angle},
+            ..Self::point(color, radius)
+        }
+    }
+}
+
+//Renders the lights in a light map and multiplies it over the scene.
+pub struct KaLighting {
+    width: u32,
+    height: u32,
+    pub ambient: Color,
+    //Collision layers that cast shadows.
+    pub shadow_mask: i32,
+    light_map: RenderTarget,
+    light_target: RenderTarget,
+    light_material: Material,
+    add_material: Material,
+    multiply_material: Material,
+}
+
+impl KaLighting {
+    //width and height of the light maps, usually the size of the camera view.
+    pub fn new(width: u32, height: u32, ambient: Color) -> KaResult<Self> {
+        let light_material = load_material(DEFAULT_VERTEX_SHADER, LIGHT_FRAGMENT_SHADER, MaterialParams {
+            uniforms: vec![
+                ("LightColor".to_owned(), UniformType::Float4),
+                ("Falloff".to_owned(), UniformType::Float1),
+                ("Intensity".to_owned(), UniformType::Float1),
+                ("Direction".to_owned(), UniformType::Float1),
+                ("Angle".to_owned(), UniformType::Float1),
+            ],
+            ..Default::default()
+        }).map_err(|e| KaError::Shader {name: "light".to_owned(), message: format!("{:?}", e)})?;
+        let add_material = Self::load_blend_material(BlendState::new(
+            Equation::Add, BlendFactor::One, BlendFactor::One,
+        ))?;
+        let multiply_material = Self::load_blend_material(BlendState::new(
+            Equation::Add, BlendFactor::Value(BlendValue::DestinationColor), BlendFactor::Zero,
+        ))?;
+        Ok(Self {
+            width,
+            height,
+            ambient,
+            shadow_mask: 0,
+            light_map: render_target(width, height),
+            light_target: render_target(width, height),
+            light_material,
+            add_material,
+            multiply_material,
+        })
+    }
+
+    fn load_blend_material(blend: BlendState) -> KaResult<Material> {
+        use crate::shaders::DEFAULT_FRAGMENT_SHADER;
+        load_material(DEFAULT_VERTEX_SHADER, DEFAULT_FRAGMENT_SHADER, MaterialParams {
+            pipeline_params: PipelineParams {
+                color_blend: Some(blend),
+                ..Default::default()
+            },
+            ..Default::default()
+        }).map_err(|e| KaError::Shader {name: "blend".to_owned(), message: format!("{:?}", e)})
+    }
+
+    pub fn get_light_map(&self) -> RenderTarget {
+        self.light_map
+    }
+
+    fn get_target_camera(&self, target: RenderTarget) -> Camera2D {
+        ka_render_target_camera(Rect::new(0.0, 0.0, self.width as f32, self.height as f32), target)
+    }
+
+    //The shadow of a box is the projection of its edges away from the light.
+    fn draw_shadow(light_pos: Vec2, radius: f32, pos: Vec2, half_e: Vec2) {
+        if KaAABB::overlaps(&light_pos, &Vec2::zero(), &pos, &half_e) {
+            return;
+        }
+        let corners = [
+            pos + Vec2::new(-half_e.x(), -half_e.y()),
+            pos + Vec2::new(half_e.x(), -half_e.y()),
+            pos + Vec2::new(half_e.x(), half_e.y()),
+            pos + Vec2::new(-half_e.x(), half_e.y()),
+        ];
+        let project = |p: Vec2| p + (p - light_pos).normalize() * radius * 2.0;
+        for i in 0..4 {
+            let (p1, p2) = (corners[i], corners[(i + 1) % 4]);
+            let (f1, f2) = (project(p1), project(p2));
+            draw_triangle(p1, p2, f2, BLACK);
+            draw_triangle(p1, f2, f1, BLACK);
+        }
+    }
+
+    //Renders the light map. Call it before drawing the scene, it changes the camera.
+    pub fn update(&self, world: &World, camera: &KaCamera) {
+        let (view_pos, view_half_e) = camera.get_aabb_data();
+        set_camera(self.get_target_camera(self.light_map));
+        clear_background(self.ambient);
+
+        for (e, light) in world.query::<&KaLight>().iter() {
+            if !light.enabled || light.intensity <= 0.0 || light.radius <= 0.0 {
+                continue;
+            }
+            let pos = ka_entity_pos(world, e).unwrap_or_else(Vec2::zero) + light.offset;
+            let r = Vec2::new(light.radius, light.radius);
+            if !KaAABB::overlaps(&pos, &r, &view_pos, &view_half_e) {
+                continue;
+            }
+
+            set_camera(camera.get_target_camera(self.light_target));
+            clear_background(BLACK);
+            let (direction, angle) = match light.kind {
+                KaLightKind::Point => (0.0, std::f32::consts::PI),
+                KaLightKind::Spot {direction, angle} => (direction, angle),
+            };
+            let c = light.color.0;
+            self.light_material.set_uniform("LightColor", [c[0] as f32 / 255.0, c[1] as f32 / 255.0, c[2] as f32 / 255.0, 1.0]);
+            self.light_material.set_uniform("Falloff", light.falloff);
+            self.light_material.set_uniform("Intensity", light.intensity);
+            self.light_material.set_uniform("Direction", direction);
+            self.light_material.set_uniform("Angle", angle);
+            gl_use_material(self.light_material);
+            draw_rectangle(pos.x() - light.radius, pos.y() - light.radius, light.radius * 2.0, light.radius * 2.0, WHITE);
+            gl_use_default_material();
+
+            if light.shadows && self.shadow_mask != 0 {
+                for (_, aabb) in world.query::<&KaAABB>().iter() {
+                    if !aabb.solid || aabb.collision_layer & self.shadow_mask == 0 {
+                        continue;
+                    }
+                    let aabb_pos = *aabb.pos.lock().unwrap();
+                    if KaAABB::overlaps(&pos, &r, &aabb_pos, &aabb.half_e) {
+                        Self::draw_shadow(pos, light.radius, aabb_pos, aabb.half_e);
+                    }
+                }
+            }
+
+            set_camera(self.get_target_camera(self.light_map));
+            gl_use_material(self.add_material);
+            self.draw_target(self.light_target);
+            gl_use_default_material();
+        }
+    }
+
+    fn draw_target(&self, target: RenderTarget) {
+        let params = DrawTextureParams {
+            dest_size: Some(Vec2::new(self.width as f32, self.height as f32)),
+            ..Default::default()
+        };
+        draw_texture_ex(target.texture, 0.0, 0.0, WHITE, params);
+    }
+
+    //Multiplies the light map over the view, call it with the scene camera set after drawing the sprites.
+    pub fn draw(&self, camera: &KaCamera) {
+        //The target includes the shake.
+        let pos = camera.get_camera().target;
+        let (w, h) = (camera.get_width(), camera.get_height());
+        let params = DrawTextureParams {
+            dest_size: Some(Vec2::new(w, h)),
+            rotation: -camera.get_camera().rotation.to_radians(),
+            ..Default::default()
+        };
+        gl_use_material(self.multiply_material);
+        draw_texture_ex(self.light_map.texture, pos.x() - w / 2.0, pos.y() - h / 2.0, WHITE, params);
+        gl_use_default_material();
+    }
+}
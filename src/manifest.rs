@@ -0,0 +1,227 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use macroquad::prelude::load_file;
+
+use crate::{KaGame, KaError, KaResult};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KaAssetKind {
+    Texture,
+    Sound {looped: bool},
+    Csv,
+    Font,
+    //The source of a shader, get it with KaGame::get_shader.
+    Shader,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KaAsset {
+    pub name: String,
+    pub path: String,
+    pub kind: KaAssetKind,
+}
+
+//The list of assets of the game. The assets are queried by name once loaded.
+#[derive(Debug, Clone, Default)]
+pub struct KaAssetManifest {
+    pub assets: Vec<KaAsset>,
+}
+
+impl KaAssetManifest {
+    pub fn new() -> Self {
+        Self {
+            assets: Vec::new(),
+        }
+    }
+
+    pub fn add(mut self, name: &str, path: &str, kind: KaAssetKind) -> Self {
+        self.assets.push(KaAsset {
+            name: name.to_owned(),
+            path: path.to_owned(),
+            kind,
+        });
+        self
+    }
+
+    pub fn texture(self, name: &str, path: &str) -> Self {
+        self.add(name, path, KaAssetKind::Texture)
+    }
+
+    pub fn sound(self, name: &str, path: &str, looped: bool) -> Self {
+        self.add(name, path, KaAssetKind::Sound {looped})
+    }
+
+    pub fn csv(self, name: &str, path: &str) -> Self {
+        self.add(name, path, KaAssetKind::Csv)
+    }
+
+    pub fn font(self, name: &str, path: &str) -> Self {
+        self.add(name, path, KaAssetKind::Font)
+    }
+
+    pub fn shader(self, name: &str, path: &str) -> Self {
+        self.add(name, path, KaAssetKind::Shader)
+    }
+
+    //One asset per line: kind,name,path and for sounds an optional looped.
+    //kind is texture, sound, csv, font or shader. Lines starting with # are ignored.
+    pub fn from_csv_bytes(path: &str, bytes: &[u8]) -> KaResult<Self> {
+        use csv::ReaderBuilder;
+        let text = std::str::from_utf8(bytes)
+            .map_err(|e| KaError::BadEncoding {path: path.to_owned(), message: e.to_string()})?;
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .comment(Some(b'#'))
+            .from_reader(text.as_bytes());
+        let mut manifest = Self::new();
+        for result in rdr.records() {
+            let mut record = result.map_err(|e| KaError::Decode {path: path.to_owned(), message: e.to_string()})?;
+            record.trim();
+            let (kind, name, asset_path) = match (record.get(0), record.get(1), record.get(2)) {
+                (Some(kind), Some(name), Some(asset_path)) => (kind, name, asset_path),
+                _ => return Err(KaError::Decode {path: path.to_owned(), message: format!("invalid line {:?}", record)}),
+            };
+            let kind = match kind {
+                "texture" => KaAssetKind::Texture,
+                "sound" => KaAssetKind::Sound {looped: record.get(3) == Some("looped")},
+                "csv" => KaAssetKind::Csv,
+                "font" => KaAssetKind::Font,
+                "shader" => KaAssetKind::Shader,
+                _ => return Err(KaError::Decode {path: path.to_owned(), message: format!("unknown asset kind \"{}\"", kind)}),
+            };
+            manifest = manifest.add(name, asset_path, kind);
+        }
+        Ok(manifest)
+    }
+
+    //Looks in the packs mounted in the game before the disk.
+    pub async fn load_from_file(game: &KaGame, path: &str) -> KaResult<Self> {
+        let bytes = game.read_file(path).await?;
+        Self::from_csv_bytes(path, &bytes)
+    }
+}
+
+type FileFuture = Pin<Box<dyn Future<Output = KaResult<Vec<u8>>>>>;
+
+fn noop_waker() -> Waker {
+    fn clone(_: *const ()) -> RawWaker {
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+    fn noop(_: *const ()) {}
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+    unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+}
+
+//Loads all the files of a manifest at the same time.
+//Call update every frame and draw get_progress until is_done.
+//The files found in the packs mounted in the game are not read from the disk.
+pub struct KaAssetLoader {
+    pending: Vec<(KaAsset, Option<FileFuture>)>,
+    total: usize,
+    loaded: usize,
+    errors: Vec<KaError>,
+}
+
+impl KaAssetLoader {
+    pub fn new(manifest: &KaAssetManifest) -> Self {
+        let pending: Vec<(KaAsset, Option<FileFuture>)> = manifest.assets.iter()
+            .map(|asset| (asset.clone(), None))
+            .collect();
+        Self {
+            total: pending.len(),
+            pending,
+            loaded: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    fn load_file(path: &str) -> FileFuture {
+        let path = path.to_owned();
+        Box::pin(async move {
+            load_file(&path)
+                .await
+                .map_err(|e| KaError::MissingAsset {path: path.clone(), message: format!("{:?}", e)})
+        })
+    }
+
+    //From 0.0 to 1.0, failed assets count as done.
+    pub fn get_progress(&self) -> f32 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        (self.total - self.pending.len()) as f32 / self.total as f32
+    }
+
+    pub fn get_loaded(&self) -> usize {
+        self.loaded
+    }
+
+    pub fn get_total(&self) -> usize {
+        self.total
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn get_errors(&self) -> &[KaError] {
+        &self.errors
+    }
+
+    //Polls the files and adds the finished ones to the game. Returns the progress.
+    pub fn update(&mut self, game: &mut KaGame) -> f32 {
+        let waker = noop_waker();
+        let mut context = Context::from_waker(&waker);
+        let mut i = 0;
+        while i < self.pending.len() {
+            let (asset, future) = &mut self.pending[i];
+            let ready = match future.as_mut() {
+                Some(future) => match future.as_mut().poll(&mut context) {
+                    Poll::Ready(result) => Some((result, true)),
+                    Poll::Pending => None,
+                },
+                None => match game.get_resources_mut().get_packed(&asset.path) {
+                    Some(bytes) => Some((Ok(bytes.to_vec()), false)),
+                    None => {
+                        //Not in a pack, read from the disk starting with the next update.
+                        *future = Some(Self::load_file(&asset.path));
+                        None
+                    },
+                },
+            };
+            match ready {
+                Some((result, from_disk)) => {
+                    let (asset, _) = self.pending.remove(i);
+                    match result.and_then(|bytes| Self::add_asset(game, &asset, &bytes, from_disk)) {
+                        Ok(()) => self.loaded += 1,
+                        Err(e) => self.errors.push(e),
+                    }
+                },
+                None => i += 1,
+            }
+        }
+        self.get_progress()
+    }
+
+    fn add_asset(game: &mut KaGame, asset: &KaAsset, bytes: &[u8], from_disk: bool) -> KaResult<()> {
+        let resources = game.get_resources_mut();
+        if from_disk {
+            resources.watch(&asset.name, &asset.path, asset.kind.clone());
+        }
+        resources.load_asset_from_bytes(asset, bytes)
+    }
+
+    //Loads everything without drawing, the errors are returned in get_errors.
+    pub async fn load_all(&mut self, game: &mut KaGame) {
+        use macroquad::prelude::next_frame;
+        while !self.is_done() {
+            self.update(game);
+            if !self.is_done() {
+                next_frame().await;
+            }
+        }
+    }
+}
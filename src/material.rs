@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use macroquad::prelude::{
+    Material, MaterialParams, UniformType, BLANK, load_material, gl_use_material, gl_use_default_material, draw_rectangle,
+};
+
+use crate::shaders::DEFAULT_VERTEX_SHADER;
+use crate::{KaError, KaResult};
+
+static NEXT_MATERIAL_ID: AtomicUsize = AtomicUsize::new(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KaUniform {
+    Float1(f32),
+    Float2([f32; 2]),
+    Float3([f32; 3]),
+    Float4([f32; 4]),
+    Int1(i32),
+}
+
+impl KaUniform {
+    pub fn get_type(&self) -> UniformType {
+        match self {
+            KaUniform::Float1(_) => UniformType::Float1,
+            KaUniform::Float2(_) => UniformType::Float2,
+            KaUniform::Float3(_) => UniformType::Float3,
+            KaUniform::Float4(_) => UniformType::Float4,
+            KaUniform::Int1(_) => UniformType::Int1,
+        }
+    }
+
+    pub fn apply(&self, material: &Material, name: &str) {
+        match self {
+            KaUniform::Float1(v) => material.set_uniform(name, *v),
+            KaUniform::Float2(v) => material.set_uniform(name, *v),
+            KaUniform::Float3(v) => material.set_uniform(name, *v),
+            KaUniform::Float4(v) => material.set_uniform(name, *v),
+            KaUniform::Int1(v) => material.set_uniform(name, *v),
+        }
+    }
+
+    //Same type with every value at 0.
+    pub fn zero(&self) -> Self {
+        match self {
+            KaUniform::Float1(_) => KaUniform::Float1(0.0),
+            KaUniform::Float2(_) => KaUniform::Float2([0.0; 2]),
+            KaUniform::Float3(_) => KaUniform::Float3([0.0; 3]),
+            KaUniform::Float4(_) => KaUniform::Float4([0.0; 4]),
+            KaUniform::Int1(_) => KaUniform::Int1(0),
+        }
+    }
+}
+
+//A material for KaSprite. The id is used to group the sprites that share the material.
+//The clones share the same shader and the same uniform values.
+#[derive(Clone)]
+pub struct KaMaterial {
+    pub id: usize,
+    pub material: Material,
+    //Values set with set_uniform, restored after the per-sprite uniforms.
+    values: Arc<Mutex<HashMap<String, KaUniform>>>,
+}
+
+impl KaMaterial {
+    //The fragment shader gets uv and color from DEFAULT_VERTEX_SHADER.
+    pub fn new(fragment_shader: &str, uniforms: &[(&str, UniformType)]) -> KaResult<Self> {
+        Self::with_vertex(DEFAULT_VERTEX_SHADER, fragment_shader, uniforms)
+    }
+
+    pub fn with_vertex(vertex_shader: &str, fragment_shader: &str, uniforms: &[(&str, UniformType)]) -> KaResult<Self> {
+        let material = load_material(vertex_shader, fragment_shader, MaterialParams {
+            uniforms: uniforms.iter().map(|(n, t)| (n.to_string(), *t)).collect(),
+            ..Default::default()
+        }).map_err(|e| KaError::Shader {name: "material".to_owned(), message: format!("{:?}", e)})?;
+        Ok(Self {
+            id: NEXT_MATERIAL_ID.fetch_add(1, Ordering::Relaxed),
+            material,
+            values: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    //The value used by all the sprites without their own value for this uniform.
+    pub fn set_uniform(&self, name: &str, value: KaUniform) {
+        self.values.lock().unwrap().insert(name.to_owned(), value);
+        value.apply(&self.material, name);
+    }
+
+    pub fn get_uniform(&self, name: &str) -> Option<KaUniform> {
+        self.values.lock().unwrap().get(name).copied()
+    }
+
+    //Only until restore_uniform, used for the uniforms of a single sprite.
+    pub fn apply_uniform(&self, name: &str, value: KaUniform) {
+        value.apply(&self.material, name);
+    }
+
+    //Goes back to the value of set_uniform, or to 0 if it was never set.
+    pub fn restore_uniform(&self, name: &str, applied: KaUniform) {
+        let value = self.get_uniform(name).unwrap_or_else(|| applied.zero());
+        value.apply(&self.material, name);
+    }
+
+    //The uniforms are taken when a draw call starts, the sprites already drawn with the material
+    //are still waiting in the current one. An empty draw with the default material ends it,
+    //the next sprite starts a new draw call with the new values.
+    fn break_batch(&self) {
+        gl_use_default_material();
+        draw_rectangle(0.0, 0.0, 0.0, 0.0, BLANK);
+        gl_use_material(self.material);
+    }
+
+    //Applies the uniforms of a sprite and restores the ones of the previous sprite it does not have.
+    //The material must be in use, the sprites drawn after it are in a new draw call.
+    pub(crate) fn swap_uniforms(&self, previous: &[(String, KaUniform)], uniforms: &[(String, KaUniform)]) {
+        if previous.is_empty() && uniforms.is_empty() {
+            return;
+        }
+        self.break_batch();
+        for (name, value) in previous.iter() {
+            if !uniforms.iter().any(|(n, _)| n == name) {
+                self.restore_uniform(name, *value);
+            }
+        }
+        for (name, value) in uniforms.iter() {
+            self.apply_uniform(name, *value);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+
+use crate::{KaError, KaResult};
+
+//Layout of a pack, all the numbers are little endian:
+//"KAPK", version: u32, count: u32,
+//count times (name length: u32, name: utf8, offset: u64, size: u64),
+//then the data of the files, the offsets start after the index.
+const MAGIC: &[u8; 4] = b"KAPK";
+const VERSION: u32 = 1;
+
+//A single file holding many assets, mount it in the game and the assets
+//are loaded from it instead of the disk, using the same paths.
+pub struct KaPack {
+    name: String,
+    data: Vec<u8>,
+    index: HashMap<String, (usize, usize)>,
+}
+
+impl KaPack {
+    //The name is used in the errors and by KaGame::unmount to find the pack.
+    pub fn from_bytes(name: &str, data: Vec<u8>) -> KaResult<Self> {
+        let decode_error = |message: &str| KaError::Decode {path: name.to_owned(), message: message.to_owned()};
+        let mut reader = Reader {data: &data, pos: 0};
+
+        if reader.read(4).ok_or_else(|| decode_error("too short"))? != MAGIC {
+            return Err(decode_error("not a pack file"));
+        }
+        let version = reader.read_u32().ok_or_else(|| decode_error("too short"))?;
+        if version != VERSION {
+            return Err(decode_error(&format!("unsupported version {}", version)));
+        }
+        let count = reader.read_u32().ok_or_else(|| decode_error("too short"))?;
+
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let len = reader.read_u32().ok_or_else(|| decode_error("truncated index"))? as usize;
+            let path = reader.read(len).ok_or_else(|| decode_error("truncated index"))?;
+            let path = std::str::from_utf8(path)
+                .map_err(|e| KaError::BadEncoding {path: name.to_owned(), message: e.to_string()})?
+                .to_owned();
+            let offset = reader.read_u64().ok_or_else(|| decode_error("truncated index"))?;
+            let size = reader.read_u64().ok_or_else(|| decode_error("truncated index"))?;
+            //u64 does not fit in the usize of 32 bits targets like wasm.
+            let offset = usize::try_from(offset).map_err(|_| decode_error("offset too big"))?;
+            let size = usize::try_from(size).map_err(|_| decode_error("size too big"))?;
+            entries.push((path, offset, size));
+        }
+
+        let start = reader.pos;
+        let mut index = HashMap::new();
+        for (path, offset, size) in entries {
+            //The offsets come from the file, a broken pack must not overflow.
+            let begin = start.checked_add(offset)
+                .filter(|begin| begin.checked_add(size).map_or(false, |end| end <= data.len()))
+                .ok_or_else(|| decode_error(&format!("{} is out of the pack", path)))?;
+            index.insert(path, (begin, size));
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            data,
+            index,
+        })
+    }
+
+    pub async fn load_from_file(path: &str) -> KaResult<Self> {
+        let data = macroquad::prelude::load_file(path)
+            .await
+            .map_err(|e| KaError::MissingAsset {path: path.to_owned(), message: format!("{:?}", e)})?;
+        Self::from_bytes(path, data)
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get(&self, path: &str) -> Option<&[u8]> {
+        self.index.get(path).map(|&(offset, size)| &self.data[offset..offset + size])
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.index.contains_key(path)
+    }
+
+    pub fn get_paths(&self) -> impl Iterator<Item = &str> {
+        self.index.keys().map(|p| p.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn read(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(len)?;
+        let bytes = self.data.get(self.pos..end)?;
+        self.pos = end;
+        Some(bytes)
+    }
+
+    fn read_u32(&mut self) -> Option<u32> {
+        self.read(4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    fn read_u64(&mut self) -> Option<u64> {
+        self.read(8).map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+    }
+}
+
+//Makes the pack files, usually from a build script or a small tool:
+//KaPackBuilder::new().add_file("assets/player.png")?.save("assets.pak")?;
+#[derive(Default)]
+pub struct KaPackBuilder {
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl KaPackBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //Adding the same path twice replaces the file.
+    pub fn add(mut self, path: &str, bytes: Vec<u8>) -> Self {
+        self.files.retain(|(p, _)| p != path);
+        self.files.push((path.to_owned(), bytes));
+        self
+    }
+
+    //Reads the file from the disk, the path is kept as the name in the pack.
+    pub fn add_file(self, path: &str) -> KaResult<Self> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| KaError::MissingAsset {path: path.to_owned(), message: e.to_string()})?;
+        Ok(self.add(path, bytes))
+    }
+
+    //Adds all the files of a folder and its subfolders, with paths like "dir/sub/file.png".
+    pub fn add_dir(mut self, dir: &str) -> KaResult<Self> {
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| KaError::MissingAsset {path: dir.to_owned(), message: e.to_string()})?;
+        let mut paths = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| KaError::MissingAsset {path: dir.to_owned(), message: e.to_string()})?;
+            paths.push(entry.path());
+        }
+        //Same pack for the same folder.
+        paths.sort();
+        for path in paths {
+            let path = format!("{}/{}", dir.trim_end_matches('/'), path.file_name().unwrap().to_string_lossy());
+            if std::path::Path::new(&path).is_dir() {
+                self = self.add_dir(&path)?;
+            } else {
+                self = self.add_file(&path)?;
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.files.len() as u32).to_le_bytes());
+        let mut offset = 0u64;
+        for (path, data) in &self.files {
+            bytes.extend_from_slice(&(path.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(path.as_bytes());
+            bytes.extend_from_slice(&offset.to_le_bytes());
+            bytes.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            offset += data.len() as u64;
+        }
+        for (_, data) in &self.files {
+            bytes.extend_from_slice(data);
+        }
+        bytes
+    }
+
+    //Mounts the files without writing the pack, handy while developing.
+    pub fn build(&self, name: &str) -> KaResult<KaPack> {
+        KaPack::from_bytes(name, self.to_bytes())
+    }
+
+    pub fn save(&self, path: &str) -> KaResult<()> {
+        std::fs::write(path, self.to_bytes())
+            .map_err(|e| KaError::MissingAsset {path: path.to_owned(), message: e.to_string()})
+    }
+}
@@ -0,0 +1,99 @@
+use macroquad::prelude::{Vec2, Color, Texture2D, WHITE, draw_texture_ex, DrawTextureParams};
+use hecs::{World, Entity};
+
+use crate::KaCamera;
+
+//A background that follows the camera. scroll (1, 1) moves like the world, (0, 0) is fixed to the screen.
+pub struct KaParallax {
+    pub visible: bool,
+    pub texture: Texture2D,
+    pub color: Color,
+    pub z: u32,
+    //Top left corner of the texture when the camera is at (0, 0).
+    pub origin: Vec2,
+    pub scale: Vec2,
+    pub scroll: Vec2,
+    pub repeat_x: bool,
+    pub repeat_y: bool,
+}
+
+impl KaParallax {
+    pub fn new(texture: Texture2D, scroll_x: f32, scroll_y: f32) -> Self {
+        Self {
+            visible: true,
+            texture,
+            color: WHITE,
+            z: 0,
+            origin: Vec2::zero(),
+            scale: Vec2::one(),
+            scroll: Vec2::new(scroll_x, scroll_y),
+            repeat_x: false,
+            repeat_y: false,
+        }
+    }
+
+    pub fn set_repeat(&mut self, repeat_x: bool, repeat_y: bool) {
+        self.repeat_x = repeat_x;
+        self.repeat_y = repeat_y;
+    }
+
+    pub fn get_size(&self) -> Vec2 {
+        Vec2::new(self.texture.width() * self.scale.x(), self.texture.height() * self.scale.y())
+    }
+
+    //Top left corner of the texture for the given camera position.
+    pub fn get_pos(&self, camera_pos: Vec2) -> Vec2 {
+        self.origin + camera_pos * (Vec2::one() - self.scroll)
+    }
+
+    //Positions of all the tiles needed to cover the view.
+    pub fn get_tiles(&self, camera: &KaCamera) -> Vec<Vec2> {
+        let (camera_pos, half_e) = camera.get_aabb_data();
+        let (min, max) = (camera_pos - half_e, camera_pos + half_e);
+        let pos = self.get_pos(camera_pos);
+        let size = self.get_size();
+        let xs = Self::get_axis_tiles(pos.x(), size.x(), min.x(), max.x(), self.repeat_x);
+        let ys = Self::get_axis_tiles(pos.y(), size.y(), min.y(), max.y(), self.repeat_y);
+        let mut tiles: Vec<Vec2> = Vec::with_capacity(xs.len() * ys.len());
+        for y in ys.iter() {
+            for x in xs.iter() {
+                tiles.push(Vec2::new(*x, *y));
+            }
+        }
+        tiles
+    }
+
+    fn get_axis_tiles(pos: f32, size: f32, min: f32, max: f32, repeat: bool) -> Vec<f32> {
+        if !repeat || size <= 0.0 {
+            return vec![pos];
+        }
+        let mut tiles: Vec<f32> = Vec::new();
+        let mut t = pos + ((min - pos) / size).floor() * size;
+        while t < max {
+            tiles.push(t);
+            t += size;
+        }
+        tiles
+    }
+}
+
+//Draws the parallax layers sorted by z, call it with the camera set and before ka_draw_sprites.
+pub fn ka_draw_parallax(world: &mut World, camera: &KaCamera) {
+    let mut q = world.query::<&KaParallax>();
+    let mut query: Vec<(Entity, &KaParallax)> = q.iter().collect();
+    query.sort_by(|a, b| a.1.z.cmp(&b.1.z).then(a.0.id().cmp(&b.0.id())) );
+
+    for (_, parallax) in query.iter() {
+        if !parallax.visible {
+            continue;
+        }
+        let size = parallax.get_size();
+        for tile in parallax.get_tiles(camera).iter() {
+            let params = DrawTextureParams {
+                dest_size: Some(size),
+                ..Default::default()
+            };
+            draw_texture_ex(parallax.texture, tile.x().floor(), tile.y().floor(), parallax.color, params);
+        }
+    }
+}
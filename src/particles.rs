@@ -0,0 +1,264 @@
+use macroquad::prelude::{Vec2, Color, Texture2D};
+use macroquad::rand::gen_range;
+use hecs::{World, Entity};
+
+use crate::{KaGame, KaSprite, KaAABB, Handle, ka_entity_pos};
+use crate::sprite::draw_sprite_texture;
+
+pub struct KaParticle {
+    pub pos: Vec2,
+    pub velocity: Vec2,
+    pub rotation: f32,
+    pub angular_velocity: f32,
+    pub life: f32,
+    pub lifetime: f32,
+}
+
+//Returns the value of the curve at t, the keys must be sorted by time.
+fn sample_curve<T: Copy>(keys: &[(f32, T)], t: f32, default: T, lerp: fn(T, T, f32) -> T) -> T {
+    match keys.len() {
+        0 => default,
+        1 => keys[0].1,
+        _ => {
+            if t <= keys[0].0 {
+                return keys[0].1;
+            }
+            for pair in keys.windows(2) {
+                let ((t0, v0), (t1, v1)) = (pair[0], pair[1]);
+                if t <= t1 {
+                    let f = if t1 > t0 {(t - t0) / (t1 - t0)} else {1.0};
+                    return lerp(v0, v1, f);
+                }
+            }
+            keys[keys.len() - 1].1
+        }
+    }
+}
+
+fn lerp_f32(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn lerp_color(a: Color, b: Color, t: f32) -> Color {
+    let mut color = a;
+    for i in 0..4 {
+        color.0[i] = lerp_f32(a.0[i] as f32, b.0[i] as f32, t).round() as u8;
+    }
+    color
+}
+
+//Emits particles from the position of the KaAABB or KaSprite of the entity plus offset.
+//The particles are drawn with the sprite, only pos, scale, color and rotation change.
+pub struct KaEmitter {
+    pub sprite: KaSprite,
+    pub emitting: bool,
+    pub offset: Vec2,
+    //Particles per second.
+    pub rate: f32,
+    //(time since start, count)
+    pub bursts: Vec<(f32, usize)>,
+    //The emitter stops after duration seconds, 0.0 emits forever.
+    pub duration: f32,
+    pub max_particles: usize,
+    pub lifetime: (f32, f32),
+    pub speed: (f32, f32),
+    //Radians, 0.0 is to the right.
+    pub direction: f32,
+    pub spread: f32,
+    pub angular_velocity: (f32, f32),
+    pub gravity: Vec2,
+    //Velocity lost per second.
+    pub damping: f32,
+    pub color_over_life: Vec<(f32, Color)>,
+    pub scale_over_life: Vec<(f32, f32)>,
+    //Particles bounce on solid KaAABBs of the collision mask.
+    pub collide: bool,
+    pub collision_mask: i32,
+    pub bounce: f32,
+    //Despawns the entity when the emitter is done and there are no particles left.
+    pub despawn_when_done: bool,
+    pub particles: Vec<KaParticle>,
+    time: f32,
+    accumulator: f32,
+    next_burst: usize,
+}
+
+impl KaEmitter {
+    pub fn new(texture: Handle<Texture2D>, rate: f32, lifetime: f32) -> Self {
+        Self {
+            sprite: KaSprite::new(texture),
+            emitting: true,
+            offset: Vec2::zero(),
+            rate,
+            bursts: Vec::new(),
+            duration: 0.0,
+            max_particles: 500,
+            lifetime: (lifetime, lifetime),
+            speed: (0.0, 0.0),
+            direction: 0.0,
+            spread: std::f32::consts::PI,
+            angular_velocity: (0.0, 0.0),
+            gravity: Vec2::zero(),
+            damping: 0.0,
+            color_over_life: Vec::new(),
+            scale_over_life: Vec::new(),
+            collide: false,
+            collision_mask: 0,
+            bounce: 0.5,
+            despawn_when_done: false,
+            particles: Vec::new(),
+            time: 0.0,
+            accumulator: 0.0,
+            next_burst: 0,
+        }
+    }
+
+    //An emitter that emits count particles once.
+    pub fn new_burst(texture: Handle<Texture2D>, count: usize, lifetime: f32) -> Self {
+        let mut emitter = Self::new(texture, 0.0, lifetime);
+        emitter.bursts.push((0.0, count));
+        emitter.duration = std::f32::EPSILON;
+        emitter.despawn_when_done = true;
+        emitter
+    }
+
+    pub fn restart(&mut self) {
+        self.time = 0.0;
+        self.accumulator = 0.0;
+        self.next_burst = 0;
+        self.emitting = true;
+    }
+
+    pub fn is_done(&self) -> bool {
+        let stopped = !self.emitting || (self.duration > 0.0 && self.time >= self.duration && self.next_burst >= self.bursts.len());
+        stopped && self.particles.is_empty()
+    }
+
+    fn random(range: (f32, f32)) -> f32 {
+        if range.1 > range.0 {
+            gen_range(range.0, range.1)
+        } else {
+            range.0
+        }
+    }
+
+    pub fn emit(&mut self, pos: Vec2, count: usize) {
+        for _ in 0..count {
+            if self.particles.len() >= self.max_particles {
+                break;
+            }
+            let angle = self.direction + Self::random((-self.spread, self.spread));
+            let speed = Self::random(self.speed);
+            self.particles.push(KaParticle {
+                pos,
+                velocity: Vec2::new(angle.cos(), angle.sin()) * speed,
+                rotation: self.sprite.rotation,
+                angular_velocity: Self::random(self.angular_velocity),
+                life: 0.0,
+                lifetime: Self::random(self.lifetime).max(std::f32::EPSILON),
+            });
+        }
+    }
+
+    fn update(&mut self, pos: Vec2, solids: &[(Vec2, Vec2, i32)], delta: f32) {
+        if self.emitting {
+            let active = self.duration <= 0.0 || self.time < self.duration;
+            self.time += delta;
+            while self.next_burst < self.bursts.len() && self.bursts[self.next_burst].0 <= self.time {
+                let count = self.bursts[self.next_burst].1;
+                self.emit(pos, count);
+                self.next_burst += 1;
+            }
+            if active && self.rate > 0.0 {
+                self.accumulator += self.rate * delta;
+                let count = self.accumulator.floor();
+                self.accumulator -= count;
+                self.emit(pos, count as usize);
+            }
+        }
+
+        let damping = (1.0 - self.damping * delta).max(0.0);
+        for particle in self.particles.iter_mut() {
+            particle.life += delta;
+            particle.velocity = (particle.velocity + self.gravity * delta) * damping;
+            particle.rotation += particle.angular_velocity * delta;
+            particle.pos += particle.velocity * delta;
+            if self.collide {
+                for (solid_pos, half_e, layer) in solids.iter() {
+                    if layer & self.collision_mask == 0 || !KaAABB::overlaps(&particle.pos, &Vec2::zero(), solid_pos, half_e) {
+                        continue;
+                    }
+                    //Push out through the closest side and bounce.
+                    let d = particle.pos - *solid_pos;
+                    let (px, py) = (half_e.x() - d.x().abs(), half_e.y() - d.y().abs());
+                    if px < py {
+                        *particle.pos.x_mut() += px * d.x().signum();
+                        *particle.velocity.x_mut() = -particle.velocity.x() * self.bounce;
+                    } else {
+                        *particle.pos.y_mut() += py * d.y().signum();
+                        *particle.velocity.y_mut() = -particle.velocity.y() * self.bounce;
+                    }
+                }
+            }
+        }
+        self.particles.retain(|p| p.life < p.lifetime);
+    }
+
+    pub fn draw(&self, game: &KaGame) {
+        use macroquad::prelude::{gl_use_material, gl_use_default_material};
+        if !self.sprite.visible || self.particles.is_empty() {
+            return;
+        }
+        let texture = match game.get_texture_from_handle(self.sprite.texture) {
+            Some(texture) => texture,
+            None => return,
+        };
+        if let Some(material) = &self.sprite.material {
+            gl_use_material(material.material);
+            material.swap_uniforms(&[], &self.sprite.uniforms);
+        }
+        let mut sprite = self.sprite.clone();
+        for particle in self.particles.iter() {
+            let t = particle.life / particle.lifetime;
+            let scale = sample_curve(&self.scale_over_life, t, 1.0, lerp_f32);
+            sprite.pos = particle.pos;
+            sprite.rotation = particle.rotation;
+            sprite.scale = self.sprite.scale * scale;
+            sprite.color = sample_curve(&self.color_over_life, t, self.sprite.color, lerp_color);
+            draw_sprite_texture(&sprite, texture);
+        }
+        if let Some(material) = &self.sprite.material {
+            material.swap_uniforms(&self.sprite.uniforms, &[]);
+            gl_use_default_material();
+        }
+    }
+}
+
+pub fn ka_particles_update(world: &mut World, game: &mut KaGame, delta: f32) {
+    let mut solids: Vec<(Vec2, Vec2, i32)> = Vec::new();
+    if world.query::<&KaEmitter>().iter().any(|(_, emitter)| emitter.collide) {
+        for (_, aabb) in world.query::<&KaAABB>().iter() {
+            if aabb.solid {
+                solids.push((*aabb.pos.lock().unwrap(), aabb.half_e, aabb.collision_layer));
+            }
+        }
+    }
+
+    for (e, emitter) in world.query::<&mut KaEmitter>().iter() {
+        let pos = ka_entity_pos(world, e).unwrap_or_else(Vec2::zero) + emitter.offset;
+        emitter.update(pos, &solids, delta);
+        if emitter.despawn_when_done && emitter.is_done() {
+            game.to_despawn(e);
+        }
+    }
+}
+
+//Draws the emitters sorted by the z of their sprite.
+pub fn ka_draw_particles(world: &mut World, game: &KaGame) {
+    let mut q = world.query::<&KaEmitter>();
+    let mut query: Vec<(Entity, &KaEmitter)> = q.iter().collect();
+    query.sort_by(|a, b| a.1.sprite.z.cmp(&b.1.sprite.z).then(a.0.id().cmp(&b.0.id())) );
+    for (_, emitter) in query.iter() {
+        emitter.draw(game);
+    }
+}
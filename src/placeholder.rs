@@ -24,13 +24,27 @@ pub fn add(game: &mut KaGame, x: f32, y: f32, w: f32, h: f32, call_fn: fn(&mut K
     game.to_spawn(e);
 }
 
+//Activates the placeholders seen by the camera, even if it is not active.
 pub fn system(world: &mut World, game: &mut KaGame, camera: &KaCamera) {
+    activate_seen(world, game, &[camera]);
+}
+
+//Activates the placeholders seen by any active camera.
+pub fn system_cameras(world: &mut World, game: &mut KaGame, cameras: &[KaCamera]) {
+    let active: Vec<&KaCamera> = cameras.iter().filter(|camera| camera.active).collect();
+    activate_seen(world, game, &active);
+}
+
+fn activate_seen(world: &mut World, game: &mut KaGame, cameras: &[&KaCamera]) {
     
     for (e, placeholder) in world.query::<&Placeholder>().iter() {
-        if KaAABB::overlaps(&placeholder.pos, &placeholder.half_e, &camera.get_pos(), &Vec2::new(camera.get_width() / 2.0, camera.get_height() / 2.0)) {
+        let seen = cameras.iter().any(|camera| {
+            let (pos, half_e) = camera.get_aabb_data();
+            KaAABB::overlaps(&placeholder.pos, &placeholder.half_e, &pos, &half_e)
+        });
+        if seen {
             (placeholder.call_fn)(game, placeholder.pos.x(), placeholder.pos.y());
             game.to_despawn(e);
         }
     }
 }
-
@@ -0,0 +1,175 @@
+use macroquad::prelude::{
+    Vec2, Rect, Material, MaterialParams, UniformType, RenderTarget, WHITE, DrawTextureParams,
+    load_material, gl_use_material, gl_use_default_material, render_target, set_camera, set_default_camera,
+    draw_texture_ex, screen_width, screen_height,
+};
+
+use crate::{KaCamera, KaError, KaResult, ka_render_target_camera};
+use crate::shaders::*;
+
+//A full screen effect. The scene is in Texture and in _ScreenTexture, _Time is (time, sin(time), cos(time), delta).
+pub struct KaPostEffect {
+    pub name: String,
+    pub material: Material,
+    pub enabled: bool,
+    uses_time: bool,
+    uses_screen_texture: bool,
+}
+
+impl KaPostEffect {
+    pub fn new(name: &str, vertex_shader: &str, fragment_shader: &str, uniforms: &[(&str, UniformType)]) -> KaResult<Self> {
+        let uses_time = fragment_shader.contains("_Time") || vertex_shader.contains("_Time");
+        let uses_screen_texture = fragment_shader.contains("_ScreenTexture");
+        let mut uniform_list: Vec<(String, UniformType)> = uniforms.iter().map(|(n, t)| (n.to_string(), *t)).collect();
+        if uses_time && !uniform_list.iter().any(|(n, _)| n == "_Time") {
+            uniform_list.push(("_Time".to_owned(), UniformType::Float4));
+        }
+        let mut textures: Vec<String> = Vec::new();
+        if uses_screen_texture {
+            textures.push("_ScreenTexture".to_owned());
+        }
+        let material = load_material(vertex_shader, fragment_shader, MaterialParams {
+            uniforms: uniform_list,
+            textures,
+            ..Default::default()
+        }).map_err(|e| KaError::Shader {name: name.to_owned(), message: format!("{:?}", e)})?;
+        Ok(Self {
+            name: name.to_owned(),
+            material,
+            enabled: true,
+            uses_time,
+            uses_screen_texture,
+        })
+    }
+
+    //Creates the effect with the default uniform values of the descriptor.
+    pub fn from_descriptor(descriptor: &KaShaderDescriptor) -> KaResult<Self> {
+        Self::from_descriptor_with(descriptor, descriptor.fragment)
+    }
+
+    //Another fragment source for the descriptor, see KaGame::load_post_effect.
+    pub fn from_descriptor_with(descriptor: &KaShaderDescriptor, fragment: &str) -> KaResult<Self> {
+        let effect = Self::new(descriptor.name, descriptor.vertex, fragment, &descriptor.get_uniform_types())?;
+        for uniform in descriptor.uniforms.iter() {
+            uniform.default.apply(&effect.material, uniform.name);
+        }
+        Ok(effect)
+    }
+
+    //Always the sources of shaders.rs, use KaGame::load_post_effect(&CRT) to edit them with the hot reload.
+    pub fn crt() -> KaResult<Self> {
+        Self::from_descriptor(&CRT)
+    }
+
+    pub fn water() -> KaResult<Self> {
+        Self::from_descriptor(&WATER)
+    }
+
+    pub fn set_uniform<T>(&self, name: &str, value: T) {
+        self.material.set_uniform(name, value);
+    }
+}
+
+//Renders the scene in a render target and applies the effects in order, the last one draws to the screen.
+pub struct KaPostProcess {
+    width: u32,
+    height: u32,
+    targets: [RenderTarget; 2],
+    pub effects: Vec<KaPostEffect>,
+    time: f32,
+}
+
+impl KaPostProcess {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            targets: [render_target(width, height), render_target(width, height)],
+            effects: Vec::new(),
+            time: 0.0,
+        }
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn add_effect(&mut self, effect: KaPostEffect) {
+        self.effects.push(effect);
+    }
+
+    pub fn get_effect(&self, name: &str) -> Option<&KaPostEffect> {
+        self.effects.iter().find(|e| e.name == name)
+    }
+
+    pub fn get_effect_mut(&mut self, name: &str) -> Option<&mut KaPostEffect> {
+        self.effects.iter_mut().find(|e| e.name == name)
+    }
+
+    pub fn remove_effect(&mut self, name: &str) {
+        self.effects.retain(|e| e.name != name);
+    }
+
+    pub fn set_effect_enabled(&mut self, name: &str, enabled: bool) {
+        if let Some(effect) = self.get_effect_mut(name) {
+            effect.enabled = enabled;
+        }
+    }
+
+    //Where the scene is drawn between begin and end.
+    pub fn get_target(&self) -> RenderTarget {
+        self.targets[0]
+    }
+
+    //Sets the camera to draw the scene in the post process.
+    pub fn begin(&self, camera: &KaCamera) {
+        set_camera(camera.get_target_camera(self.targets[0]));
+    }
+
+    //Applies the effects and draws the result to the screen.
+    pub fn end(&mut self, delta: f32) {
+        self.time += delta;
+        let time = [self.time, self.time.sin(), self.time.cos(), delta];
+        let effects: Vec<&KaPostEffect> = self.effects.iter().filter(|e| e.enabled).collect();
+        let mut source = 0;
+        for (i, effect) in effects.iter().enumerate() {
+            let last = i + 1 == effects.len();
+            if last {
+                set_default_camera();
+            } else {
+                set_camera(ka_render_target_camera(Rect::new(0.0, 0.0, self.width as f32, self.height as f32), self.targets[1 - source]));
+            }
+            if effect.uses_time {
+                effect.material.set_uniform("_Time", time);
+            }
+            if effect.uses_screen_texture {
+                effect.material.set_texture("_ScreenTexture", self.targets[source].texture);
+            }
+            gl_use_material(effect.material);
+            self.draw_target(source, last);
+            gl_use_default_material();
+            source = 1 - source;
+        }
+        if effects.is_empty() {
+            set_default_camera();
+            self.draw_target(source, true);
+        }
+    }
+
+    fn draw_target(&self, index: usize, to_screen: bool) {
+        let size = if to_screen {
+            Vec2::new(screen_width(), screen_height())
+        } else {
+            Vec2::new(self.width as f32, self.height as f32)
+        };
+        let params = DrawTextureParams {
+            dest_size: Some(size),
+            ..Default::default()
+        };
+        draw_texture_ex(self.targets[index].texture, 0.0, 0.0, WHITE, params);
+    }
+}
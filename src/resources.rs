@@ -1,24 +1,56 @@
 use std::collections::HashMap;
-use macroquad::prelude::{Texture2D, load_texture, set_texture_filter, FilterMode};
+use macroquad::prelude::{Texture2D, Image, Font, load_file, load_ttf_font_from_bytes, set_texture_filter, FilterMode};
 
-use quad_snd::{
-    decoder::{read_ogg},
-    mixer::{Sound, PlaybackStyle}
-};
+use quad_snd::mixer::{Sound, PlaybackStyle};
 
+use crate::{ka_decode_sound, KaError, KaResult, KaAssets, Handle, KaAsset, KaAssetKind, KaPack};
+use crate::csv_loader::read_csv;
+
+
+//macroquad panics with a broken image, the image is decoded here to return an error instead.
+pub(crate) fn decode_image(path: &str, bytes: &[u8]) -> KaResult<Image> {
+    let image = image::load_from_memory(bytes)
+        .map_err(|e| KaError::Decode {path: path.to_owned(), message: e.to_string()})?
+        .to_rgba();
+    let (width, height) = image.dimensions();
+    Ok(Image {
+        width: width as u16,
+        height: height as u16,
+        bytes: image.into_raw(),
+    })
+}
+
+//The font is checked before macroquad loads it, for the same reason.
+pub(crate) fn decode_font(path: &str, bytes: &[u8]) -> KaResult<Font> {
+    ttf_parser::Face::from_slice(bytes, 0)
+        .map_err(|e| KaError::Decode {path: path.to_owned(), message: e.to_string()})?;
+    Ok(load_ttf_font_from_bytes(bytes))
+}
 
 pub struct Resources {
     texture_filter_mode: FilterMode,
-    textures: HashMap<String, Texture2D>,
-    sounds: HashMap<String, Sound>,
+    textures: KaAssets<Texture2D>,
+    sounds: KaAssets<Sound>,
+    csv: HashMap<String, Vec<csv::StringRecord>>,
+    fonts: HashMap<String, Font>,
+    shaders: HashMap<String, String>,
+    packs: Vec<KaPack>,
+    #[cfg(feature = "hot-reload")]
+    watched: Vec<KaAsset>,
 }
 
 impl Resources {
     pub fn new() -> Self {
         Self {
             texture_filter_mode: FilterMode::Linear,
-            textures: HashMap::with_capacity(100),
-            sounds: HashMap::with_capacity(100),
+            textures: KaAssets::new(),
+            sounds: KaAssets::new(),
+            csv: HashMap::new(),
+            fonts: HashMap::new(),
+            shaders: HashMap::new(),
+            packs: Vec::new(),
+            #[cfg(feature = "hot-reload")]
+            watched: Vec::new(),
             
         }
     }
@@ -28,40 +60,228 @@ impl Resources {
         self.texture_filter_mode = filter;
     }
     
-    pub async fn load_texture(&mut self, path: &str) -> Texture2D {
-        let texture = load_texture(&path).await;
+    pub async fn load_texture(&mut self, path: &str) -> KaResult<Handle<Texture2D>> {
+        if let Some(bytes) = self.get_packed(path).map(|b| b.to_vec()) {
+            return self.load_texture_from_bytes(path, &bytes);
+        }
+        let bytes = load_file(path)
+            .await
+            .map_err(|e| KaError::MissingAsset {path: path.to_owned(), message: format!("{:?}", e)})?;
+        self.watch(path, path, KaAssetKind::Texture);
+        self.load_texture_from_bytes(path, &bytes)
+    }
+
+    //The last mounted pack is searched first, so a patch pack can override some files.
+    pub fn mount(&mut self, pack: KaPack) {
+        self.packs.push(pack);
+    }
+
+    pub fn unmount(&mut self, name: &str) -> Option<KaPack> {
+        let index = self.packs.iter().position(|p| p.get_name() == name)?;
+        Some(self.packs.remove(index))
+    }
+
+    pub fn get_packed(&self, path: &str) -> Option<&[u8]> {
+        self.packs.iter().rev().find_map(|p| p.get(path))
+    }
+
+    //From the mounted packs, or from the disk if no pack has it.
+    pub async fn read_file(&self, path: &str) -> KaResult<Vec<u8>> {
+        if let Some(bytes) = self.get_packed(path) {
+            return Ok(bytes.to_vec());
+        }
+        load_file(path)
+            .await
+            .map_err(|e| KaError::MissingAsset {path: path.to_owned(), message: format!("{:?}", e)})
+    }
+
+    //Like read_file, the files read from the disk are watched by the hot reload.
+    pub(crate) async fn read_file_watched(&mut self, path: &str, kind: KaAssetKind) -> KaResult<Vec<u8>> {
+        if let Some(bytes) = self.get_packed(path) {
+            return Ok(bytes.to_vec());
+        }
+        let bytes = load_file(path)
+            .await
+            .map_err(|e| KaError::MissingAsset {path: path.to_owned(), message: format!("{:?}", e)})?;
+        self.watch(path, path, kind);
+        Ok(bytes)
+    }
+
+    //Remembers where an asset comes from for the hot reload.
+    #[allow(unused_variables)]
+    pub(crate) fn watch(&mut self, name: &str, path: &str, kind: KaAssetKind) {
+        #[cfg(feature = "hot-reload")]
+        self.watched.push(KaAsset {
+            name: name.to_owned(),
+            path: path.to_owned(),
+            kind,
+        });
+    }
+
+    #[cfg(feature = "hot-reload")]
+    pub(crate) fn take_watched(&mut self) -> Vec<KaAsset> {
+        std::mem::replace(&mut self.watched, Vec::new())
+    }
+
+    //Keeps the handle of the texture, returns the old texture.
+    #[cfg(feature = "hot-reload")]
+    pub(crate) fn replace_texture(&mut self, name: &str, texture: Texture2D) -> Option<Texture2D> {
         set_texture_filter(texture, self.texture_filter_mode);
-        self.textures.insert(path.to_owned(), texture);
-        texture
+        self.textures.insert(name, texture).1
     }
 
-    /*
-    pub fn load_texture_from_bytes(&mut self, name: &str, bytes: &[u8]) -> Texture2D{
-        use macroquad::prelude::load_texture_from_bytes;
-        let texture = load_texture_from_bytes(bytes);
+    //Loading a name again deletes the old texture, the handle stays the same.
+    pub fn load_texture_from_bytes(&mut self, name: &str, bytes: &[u8]) -> KaResult<Handle<Texture2D>> {
+        let texture = Texture2D::from_image(&decode_image(name, bytes)?);
         set_texture_filter(texture, self.texture_filter_mode);
-        self.textures.insert(name.to_owned(), texture);
-        texture
+        let (handle, old) = self.textures.insert(name, texture);
+        if let Some(old) = old {
+            old.delete();
+        }
+        Ok(handle)
+    }
+
+    pub(crate) fn insert_csv(&mut self, name: &str, records: Vec<csv::StringRecord>) {
+        self.csv.insert(name.to_owned(), records);
+    }
+
+    pub fn get_csv(&self, name: &str) -> Option<&Vec<csv::StringRecord>> {
+        self.csv.get(name)
+    }
+
+    pub(crate) fn insert_font(&mut self, name: &str, font: Font) {
+        self.fonts.insert(name.to_owned(), font);
+    }
+
+    pub fn get_font(&self, name: &str) -> Option<&Font> {
+        self.fonts.get(name)
+    }
+
+    pub(crate) fn insert_shader(&mut self, name: &str, source: String) {
+        self.shaders.insert(name.to_owned(), source);
+    }
+
+    pub fn get_shader(&self, name: &str) -> Option<&str> {
+        self.shaders.get(name).map(|s| s.as_str())
+    }
+
+    pub fn load_csv_from_bytes(&mut self, name: &str, bytes: &[u8]) -> KaResult<()> {
+        let records = read_csv(name, bytes)?;
+        self.insert_csv(name, records);
+        Ok(())
+    }
+
+    pub fn load_font_from_bytes(&mut self, name: &str, bytes: &[u8]) -> KaResult<()> {
+        let font = decode_font(name, bytes)?;
+        self.insert_font(name, font);
+        Ok(())
+    }
+
+    pub fn load_shader_from_bytes(&mut self, name: &str, bytes: &[u8]) -> KaResult<()> {
+        let source = String::from_utf8(bytes.to_vec())
+            .map_err(|e| KaError::BadEncoding {path: name.to_owned(), message: e.to_string()})?;
+        self.insert_shader(name, source);
+        Ok(())
+    }
+
+    //Used by the manifest loader, the errors use the path of the asset.
+    pub(crate) fn load_asset_from_bytes(&mut self, asset: &KaAsset, bytes: &[u8]) -> KaResult<()> {
+        match asset.kind {
+            KaAssetKind::Texture => {
+                self.load_texture_from_bytes(&asset.name, bytes)?;
+            },
+            KaAssetKind::Sound {looped} => {
+                self.load_sound(&asset.name, bytes, looped)?;
+            },
+            KaAssetKind::Csv => {
+                let records = read_csv(&asset.path, bytes)?;
+                self.insert_csv(&asset.name, records);
+            },
+            KaAssetKind::Font => {
+                self.load_font_from_bytes(&asset.name, bytes)?;
+            },
+            KaAssetKind::Shader => {
+                let source = String::from_utf8(bytes.to_vec())
+                    .map_err(|e| KaError::BadEncoding {path: asset.path.clone(), message: e.to_string()})?;
+                self.insert_shader(&asset.name, source);
+            },
+        }
+        Ok(())
     }
-    */
 
 
     pub fn get_texture(&self, path: &str)-> Option<&Texture2D> {
-        self.textures.get(path)
+        self.textures.get_by_name(path)
+    }
+
+    pub fn get_texture_handle(&self, path: &str) -> Option<Handle<Texture2D>> {
+        self.textures.get_handle(path)
+    }
+
+    pub fn get_texture_from_handle(&self, handle: Handle<Texture2D>) -> Option<&Texture2D> {
+        self.textures.get(handle)
+    }
+
+    pub fn add_texture_ref(&mut self, handle: Handle<Texture2D>) {
+        self.textures.add_ref(handle);
+    }
+
+    pub fn release_texture(&mut self, handle: Handle<Texture2D>) {
+        self.textures.release(handle);
+    }
+
+    //Deletes the textures without references.
+    pub fn unload_unused_textures(&mut self) {
+        for texture in self.textures.unload_unused() {
+            texture.delete();
+        }
     }
 
     
-    pub fn load_ogg(&mut self, name: &str, data: &[u8], looped: bool) {
-        let mut sound = read_ogg(data).unwrap();
+    //OGG or WAV, detected from the data.
+    pub fn load_sound(&mut self, name: &str, data: &[u8], looped: bool) -> KaResult<Handle<Sound>> {
+        let mut sound = ka_decode_sound(name, data)?;
         if looped {
             sound.playback_style = PlaybackStyle::Looped;
         }
         
-        self.sounds.insert(name.to_owned(), sound);
+        Ok(self.sounds.insert(name, sound).0)
+    }
+
+    pub fn load_ogg(&mut self, name: &str, data: &[u8], looped: bool) -> KaResult<Handle<Sound>> {
+        self.load_sound(name, data, looped)
     }
 
     pub fn get_sound(&self, name: &str) -> Option<&Sound> {
-        self.sounds.get(name)
+        self.sounds.get_by_name(name)
+    }
+
+    pub fn get_sound_handle(&self, name: &str) -> Option<Handle<Sound>> {
+        self.sounds.get_handle(name)
+    }
+
+    pub fn get_sound_from_handle(&self, handle: Handle<Sound>) -> Option<&Sound> {
+        self.sounds.get(handle)
+    }
+
+    pub fn add_sound_ref(&mut self, handle: Handle<Sound>) {
+        self.sounds.add_ref(handle);
+    }
+
+    pub fn release_sound(&mut self, handle: Handle<Sound>) {
+        self.sounds.release(handle);
+    }
+
+    pub fn unload_unused_sounds(&mut self) {
+        self.sounds.unload_unused();
+    }
+
+    pub fn try_get_texture(&self, path: &str) -> KaResult<&Texture2D> {
+        self.get_texture(path).ok_or_else(|| KaError::UnknownName(path.to_owned()))
+    }
+
+    pub fn try_get_sound(&self, name: &str) -> KaResult<&Sound> {
+        self.get_sound(name).ok_or_else(|| KaError::UnknownName(name.to_owned()))
     }
     
 }
\ No newline at end of file
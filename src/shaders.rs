@@ -1,3 +1,6 @@
+use macroquad::prelude::UniformType;
+use crate::{KaUniform, KaMaterial, KaError, KaResult};
+
 pub const DEFAULT_FRAGMENT_SHADER: &'static str = "#version 100
 varying lowp vec4 color;
 varying lowp vec2 uv;
@@ -25,20 +28,54 @@ void main() {
 }
 ";
 
+//Used by KaLighting, draws one light in a quad of the size of its radius.
+pub const LIGHT_FRAGMENT_SHADER: &'static str = "#version 100
+precision mediump float;
+
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform vec4 LightColor;
+uniform float Falloff;
+uniform float Intensity;
+uniform float Direction;
+uniform float Angle;
+
+void main() {
+    vec2 d = uv * 2.0 - 1.0;
+    float dist = length(d);
+    float a = pow(clamp(1.0 - dist, 0.0, 1.0), Falloff) * Intensity;
+    if (Angle < 3.14159) {
+        float diff = abs(mod(atan(d.y, d.x) - Direction + 3.14159, 6.28318) - 3.14159);
+        a *= 1.0 - smoothstep(Angle * 0.85, Angle, diff);
+    }
+    gl_FragColor = vec4(LightColor.rgb * a, 1.0);
+}
+";
+
+//_Time grows all the game, lowp loses it after a few seconds.
 pub const CRT_FRAGMENT_SHADER: &'static str = r#"#version 100
-precision lowp float;
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+precision highp float;
+#else
+precision mediump float;
+#endif
 
 varying vec4 color;
 varying vec2 uv;
     
 uniform sampler2D Texture;
+uniform vec4 _Time;
+uniform float Curvature;
+uniform float ScanlineCount;
+uniform float GrilleCount;
 
 // https://www.shadertoy.com/view/XtlSD7
 
 vec2 CRTCurveUV(vec2 uv)
 {
     uv = uv * 2.0 - 1.0;
-    vec2 offset = abs( uv.yx ) / vec2( 6.0, 4.0 );
+    vec2 offset = abs( uv.yx ) / vec2( 6.0, 4.0 ) * Curvature;
     uv = uv + uv * offset * offset;
     uv = uv * 0.5 + 0.5;
     return uv;
@@ -54,9 +91,9 @@ void DrawVignette( inout vec3 color, vec2 uv )
 
 void DrawScanline( inout vec3 color, vec2 uv )
 {
-    float iTime = 0.1;
-    float scanline 	= clamp( 0.95 + 0.05 * cos( 3.14 * ( uv.y + 0.008 * iTime ) * 240.0 * 1.0 ), 0.0, 1.0 );
-    float grille 	= 0.85 + 0.15 * clamp( 1.5 * cos( 3.14 * uv.x * 640.0 * 1.0 ), 0.0, 1.0 );    
+    float iTime = _Time.x;
+    float scanline 	= clamp( 0.95 + 0.05 * cos( 3.14 * ( uv.y + 0.008 * iTime ) * ScanlineCount * 1.0 ), 0.0, 1.0 );
+    float grille 	= 0.85 + 0.15 * clamp( 1.5 * cos( 3.14 * uv.x * GrilleCount * 1.0 ), 0.0, 1.0 );    
     color *= scanline * grille * 1.2;
 }
 
@@ -97,7 +134,11 @@ void main() {
 
 
 pub const WATER_FRAGMENT_SHADER: &'static str = "#version 100
-precision lowp float;
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+precision highp float;
+#else
+precision mediump float;
+#endif
 
 varying vec2 uv;
 varying vec2 uv1;
@@ -105,8 +146,7 @@ varying vec2 uv1;
 uniform vec4 _Time;    
 uniform sampler2D Texture;
 uniform sampler2D _ScreenTexture;
-
-#define amp 0.02
+uniform float Amplitude;
 
 void main() {
     vec2 p = uv;
@@ -116,8 +156,8 @@ void main() {
     h.x += sin(h.y * 15. + time * 2.) / 30.;
     h.y += cos(h.x * 10. + time * 2.) / 30.;
             
-    p.x += sin((h.y + h.x) * 15. + time * 2.) / (400. + (10. * sin(time)));
-    p.y += cos((h.y + h.x) * 15. + time * 2.) / (400. + (10. * sin(time)));
+    p.x += sin((h.y + h.x) * 15. + time * 2.) * Amplitude / (1. + 0.025 * sin(time));
+    p.y += cos((h.y + h.x) * 15. + time * 2.) * Amplitude / (1. + 0.025 * sin(time));
 
     vec3 res = texture2D(_ScreenTexture, p).rgb * vec3(0.8, 0.8, 0.9) + vec3(0.0, 0.0, 0.04 * sin(h.y * 15. + time * 2.)) * cos(h.x * 10. + time * 2.);
 
@@ -143,3 +183,299 @@ void main() {
 
     gl_Position = res;
 }";
+
+
+pub const PALETTE_SWAP_FRAGMENT_SHADER: &'static str = "#version 100
+precision mediump float;
+
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+uniform vec4 From0;
+uniform vec4 From1;
+uniform vec4 From2;
+uniform vec4 From3;
+uniform vec4 To0;
+uniform vec4 To1;
+uniform vec4 To2;
+uniform vec4 To3;
+uniform float Tolerance;
+
+vec3 swap(vec3 c, vec4 from, vec4 to) {
+    if (distance(c, from.rgb) < Tolerance) {
+        return to.rgb;
+    }
+    return c;
+}
+
+void main() {
+    vec4 c = texture2D(Texture, uv);
+    vec3 res = swap(c.rgb, From0, To0);
+    res = swap(res, From1, To1);
+    res = swap(res, From2, To2);
+    res = swap(res, From3, To3);
+    gl_FragColor = color * vec4(res, c.a);
+}
+";
+
+pub const OUTLINE_FRAGMENT_SHADER: &'static str = "#version 100
+precision mediump float;
+
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+uniform vec4 OutlineColor;
+uniform vec2 TextureSize;
+uniform float Thickness;
+
+void main() {
+    vec4 c = color * texture2D(Texture, uv);
+    vec2 texel = Thickness / TextureSize;
+    float a = texture2D(Texture, uv + vec2(texel.x, 0.0)).a;
+    a = max(a, texture2D(Texture, uv - vec2(texel.x, 0.0)).a);
+    a = max(a, texture2D(Texture, uv + vec2(0.0, texel.y)).a);
+    a = max(a, texture2D(Texture, uv - vec2(0.0, texel.y)).a);
+    if (c.a < 0.5 && a >= 0.5) {
+        c = OutlineColor;
+    }
+    gl_FragColor = c;
+}
+";
+
+pub const HIT_FLASH_FRAGMENT_SHADER: &'static str = "#version 100
+precision mediump float;
+
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+uniform vec4 FlashColor;
+uniform float FlashAmount;
+
+void main() {
+    vec4 c = color * texture2D(Texture, uv);
+    gl_FragColor = vec4(mix(c.rgb, FlashColor.rgb, FlashAmount), c.a);
+}
+";
+
+pub const DISSOLVE_FRAGMENT_SHADER: &'static str = "#version 100
+precision mediump float;
+
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+uniform float Threshold;
+uniform float EdgeWidth;
+uniform vec4 EdgeColor;
+uniform float NoiseScale;
+
+void main() {
+    vec4 c = color * texture2D(Texture, uv);
+    float n = fract(sin(dot(floor(uv * NoiseScale), vec2(12.9898, 78.233))) * 43758.5453);
+    if (n < Threshold) {
+        discard;
+    }
+    if (n < Threshold + EdgeWidth) {
+        c.rgb = EdgeColor.rgb;
+    }
+    gl_FragColor = c;
+}
+";
+
+pub const PIXELATE_FRAGMENT_SHADER: &'static str = "#version 100
+precision mediump float;
+
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+uniform vec2 Resolution;
+
+void main() {
+    vec2 p = (floor(uv * Resolution) + 0.5) / Resolution;
+    gl_FragColor = color * texture2D(Texture, p);
+}
+";
+
+pub const CHROMATIC_ABERRATION_FRAGMENT_SHADER: &'static str = "#version 100
+precision mediump float;
+
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+uniform vec2 Offset;
+
+void main() {
+    vec4 c = texture2D(Texture, uv);
+    float r = texture2D(Texture, uv + Offset).r;
+    float b = texture2D(Texture, uv - Offset).b;
+    gl_FragColor = color * vec4(r, c.g, b, c.a);
+}
+";
+
+pub const GREYSCALE_FRAGMENT_SHADER: &'static str = "#version 100
+precision mediump float;
+
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+uniform float Amount;
+
+void main() {
+    vec4 c = color * texture2D(Texture, uv);
+    float grey = dot(c.rgb, vec3(0.299, 0.587, 0.114));
+    gl_FragColor = vec4(mix(c.rgb, vec3(grey), Amount), c.a);
+}
+";
+
+pub struct KaUniformDescriptor {
+    pub name: &'static str,
+    pub default: KaUniform,
+}
+
+//An effect with its uniforms and their default values. _Time is not listed, KaPostEffect adds it.
+pub struct KaShaderDescriptor {
+    pub name: &'static str,
+    pub vertex: &'static str,
+    pub fragment: &'static str,
+    pub uniforms: &'static [KaUniformDescriptor],
+}
+
+impl KaShaderDescriptor {
+    pub fn get_uniform_types(&self) -> Vec<(&'static str, UniformType)> {
+        self.uniforms.iter().map(|u| (u.name, u.default.get_type())).collect()
+    }
+
+    pub fn get_default(&self, name: &str) -> Option<KaUniform> {
+        self.uniforms.iter().find(|u| u.name == name).map(|u| u.default)
+    }
+
+    //A sprite material with the default values set.
+    pub fn load_material(&self) -> KaResult<KaMaterial> {
+        self.load_material_with(self.fragment)
+    }
+
+    //Same uniforms with another fragment source, KaGame::load_shader_material
+    //uses it for the sources from the shader folder of the hot reload.
+    pub fn load_material_with(&self, fragment: &str) -> KaResult<KaMaterial> {
+        let material = KaMaterial::with_vertex(self.vertex, fragment, &self.get_uniform_types())
+            .map_err(|e| match e {
+                KaError::Shader {message, ..} => KaError::Shader {name: self.name.to_owned(), message},
+                e => e,
+            })?;
+        for uniform in self.uniforms.iter() {
+            material.set_uniform(uniform.name, uniform.default);
+        }
+        Ok(material)
+    }
+}
+
+pub const CRT: KaShaderDescriptor = KaShaderDescriptor {
+    name: "crt",
+    vertex: CRT_VERTEX_SHADER,
+    fragment: CRT_FRAGMENT_SHADER,
+    uniforms: &[
+        KaUniformDescriptor {name: "Curvature", default: KaUniform::Float1(1.0)},
+        KaUniformDescriptor {name: "ScanlineCount", default: KaUniform::Float1(240.0)},
+        KaUniformDescriptor {name: "GrilleCount", default: KaUniform::Float1(640.0)},
+    ],
+};
+
+pub const WATER: KaShaderDescriptor = KaShaderDescriptor {
+    name: "water",
+    vertex: WATER_VERTEX_SHADER,
+    fragment: WATER_FRAGMENT_SHADER,
+    uniforms: &[
+        KaUniformDescriptor {name: "Amplitude", default: KaUniform::Float1(0.0025)},
+    ],
+};
+
+pub const PALETTE_SWAP: KaShaderDescriptor = KaShaderDescriptor {
+    name: "palette_swap",
+    vertex: DEFAULT_VERTEX_SHADER,
+    fragment: PALETTE_SWAP_FRAGMENT_SHADER,
+    uniforms: &[
+        KaUniformDescriptor {name: "From0", default: KaUniform::Float4([0.0, 0.0, 0.0, 1.0])},
+        KaUniformDescriptor {name: "From1", default: KaUniform::Float4([0.0, 0.0, 0.0, 1.0])},
+        KaUniformDescriptor {name: "From2", default: KaUniform::Float4([0.0, 0.0, 0.0, 1.0])},
+        KaUniformDescriptor {name: "From3", default: KaUniform::Float4([0.0, 0.0, 0.0, 1.0])},
+        KaUniformDescriptor {name: "To0", default: KaUniform::Float4([0.0, 0.0, 0.0, 1.0])},
+        KaUniformDescriptor {name: "To1", default: KaUniform::Float4([0.0, 0.0, 0.0, 1.0])},
+        KaUniformDescriptor {name: "To2", default: KaUniform::Float4([0.0, 0.0, 0.0, 1.0])},
+        KaUniformDescriptor {name: "To3", default: KaUniform::Float4([0.0, 0.0, 0.0, 1.0])},
+        KaUniformDescriptor {name: "Tolerance", default: KaUniform::Float1(0.01)},
+    ],
+};
+
+pub const OUTLINE: KaShaderDescriptor = KaShaderDescriptor {
+    name: "outline",
+    vertex: DEFAULT_VERTEX_SHADER,
+    fragment: OUTLINE_FRAGMENT_SHADER,
+    uniforms: &[
+        KaUniformDescriptor {name: "OutlineColor", default: KaUniform::Float4([1.0, 1.0, 1.0, 1.0])},
+        KaUniformDescriptor {name: "TextureSize", default: KaUniform::Float2([32.0, 32.0])},
+        KaUniformDescriptor {name: "Thickness", default: KaUniform::Float1(1.0)},
+    ],
+};
+
+pub const HIT_FLASH: KaShaderDescriptor = KaShaderDescriptor {
+    name: "hit_flash",
+    vertex: DEFAULT_VERTEX_SHADER,
+    fragment: HIT_FLASH_FRAGMENT_SHADER,
+    uniforms: &[
+        KaUniformDescriptor {name: "FlashColor", default: KaUniform::Float4([1.0, 1.0, 1.0, 1.0])},
+        KaUniformDescriptor {name: "FlashAmount", default: KaUniform::Float1(0.0)},
+    ],
+};
+
+pub const DISSOLVE: KaShaderDescriptor = KaShaderDescriptor {
+    name: "dissolve",
+    vertex: DEFAULT_VERTEX_SHADER,
+    fragment: DISSOLVE_FRAGMENT_SHADER,
+    uniforms: &[
+        KaUniformDescriptor {name: "Threshold", default: KaUniform::Float1(0.0)},
+        KaUniformDescriptor {name: "EdgeWidth", default: KaUniform::Float1(0.05)},
+        KaUniformDescriptor {name: "EdgeColor", default: KaUniform::Float4([1.0, 0.5, 0.0, 1.0])},
+        KaUniformDescriptor {name: "NoiseScale", default: KaUniform::Float1(32.0)},
+    ],
+};
+
+pub const PIXELATE: KaShaderDescriptor = KaShaderDescriptor {
+    name: "pixelate",
+    vertex: DEFAULT_VERTEX_SHADER,
+    fragment: PIXELATE_FRAGMENT_SHADER,
+    uniforms: &[
+        KaUniformDescriptor {name: "Resolution", default: KaUniform::Float2([160.0, 90.0])},
+    ],
+};
+
+pub const CHROMATIC_ABERRATION: KaShaderDescriptor = KaShaderDescriptor {
+    name: "chromatic_aberration",
+    vertex: DEFAULT_VERTEX_SHADER,
+    fragment: CHROMATIC_ABERRATION_FRAGMENT_SHADER,
+    uniforms: &[
+        KaUniformDescriptor {name: "Offset", default: KaUniform::Float2([0.002, 0.0])},
+    ],
+};
+
+pub const GREYSCALE: KaShaderDescriptor = KaShaderDescriptor {
+    name: "greyscale",
+    vertex: DEFAULT_VERTEX_SHADER,
+    fragment: GREYSCALE_FRAGMENT_SHADER,
+    uniforms: &[
+        KaUniformDescriptor {name: "Amount", default: KaUniform::Float1(1.0)},
+    ],
+};
+
+pub const EFFECTS: &[&KaShaderDescriptor] = &[
+    &CRT, &WATER, &PALETTE_SWAP, &OUTLINE, &HIT_FLASH, &DISSOLVE, &PIXELATE, &CHROMATIC_ABERRATION, &GREYSCALE,
+];
+
+pub fn get_effect(name: &str) -> Option<&'static KaShaderDescriptor> {
+    EFFECTS.iter().find(|e| e.name == name).copied()
+}
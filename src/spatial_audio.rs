@@ -0,0 +1,198 @@
+use macroquad::prelude::Vec2;
+use hecs::World;
+
+use crate::{KaGame, KaCamera, ka_entity_pos, KaPlayOptions, KaSoundHandle, KaError};
+
+//How the volume goes down between min_distance and max_distance.
+#[derive(Debug, Clone, Copy)]
+pub enum KaRolloff {
+    Linear,
+    //Like the real world, min_distance / distance, moved down to reach 0.0 at max_distance.
+    Inverse,
+    //(1 - t) ^ exponent, higher exponents fade faster near the listener.
+    Exponential(f32),
+    //Takes t from 0.0 (min_distance) to 1.0 (max_distance), returns the volume.
+    Custom(fn(f32) -> f32),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct KaSpatial {
+    //Full volume closer than this.
+    pub min_distance: f32,
+    //Silent further than this.
+    pub max_distance: f32,
+    pub rolloff: KaRolloff,
+    //Horizontal distance where the sound is fully on one side.
+    pub pan_distance: f32,
+}
+
+impl Default for KaSpatial {
+    fn default() -> Self {
+        Self {
+            min_distance: 64.0,
+            max_distance: 640.0,
+            rolloff: KaRolloff::Linear,
+            pan_distance: 320.0,
+        }
+    }
+}
+
+impl KaSpatial {
+    pub fn get_volume(&self, distance: f32) -> f32 {
+        if distance <= self.min_distance {
+            return 1.0;
+        }
+        if distance >= self.max_distance {
+            return 0.0;
+        }
+        let t = (distance - self.min_distance) / (self.max_distance - self.min_distance);
+        let volume = match self.rolloff {
+            KaRolloff::Linear => 1.0 - t,
+            KaRolloff::Inverse => {
+                let min = self.min_distance.max(1.0);
+                let end = min / self.max_distance.max(1.0);
+                (min / distance.max(1.0) - end) / (1.0 - end)
+            },
+            KaRolloff::Exponential(exponent) => (1.0 - t).powf(exponent),
+            KaRolloff::Custom(curve) => curve(t),
+        };
+        volume.max(0.0).min(1.0)
+    }
+
+    pub fn get_pan(&self, offset_x: f32) -> f32 {
+        if self.pan_distance <= 0.0 {
+            return 0.0;
+        }
+        (offset_x / self.pan_distance).max(-1.0).min(1.0)
+    }
+
+    //Volume and pan of a sound at pos heard from listener.
+    pub fn get_volume_pan(&self, pos: Vec2, listener: Vec2) -> (f32, f32) {
+        let offset = pos - listener;
+        (self.get_volume(offset.length()), self.get_pan(offset.x()))
+    }
+}
+
+//Plays a sound from the position of the entity, its KaAABB or KaSprite plus the offset.
+//The volume and the pan follow the entity and the camera every frame.
+pub struct KaSoundEmitter {
+    pub sound: String,
+    pub options: KaPlayOptions,
+    pub spatial: KaSpatial,
+    pub offset: Vec2,
+    pub playing: bool,
+    //Removes the entity when a sound that is not looped ends.
+    pub despawn_when_done: bool,
+    handle: Option<KaSoundHandle>,
+}
+
+impl KaSoundEmitter {
+    //Starts playing with the next update.
+    pub fn new(sound: &str) -> Self {
+        Self {
+            sound: sound.to_owned(),
+            options: KaPlayOptions::default(),
+            spatial: KaSpatial::default(),
+            offset: Vec2::zero(),
+            playing: true,
+            despawn_when_done: false,
+            handle: None,
+        }
+    }
+
+    pub fn looped(sound: &str) -> Self {
+        let mut emitter = Self::new(sound);
+        emitter.options.looped = Some(true);
+        emitter
+    }
+
+    pub fn play(&mut self) {
+        self.playing = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn get_handle(&self) -> Option<KaSoundHandle> {
+        self.handle
+    }
+}
+
+//The listener is the position of the camera, call it every frame after moving the entities
+//and before KaGame::update_audio, which sends the new volumes to the mixer.
+//Returns the errors of the sounds that could not be played, those emitters are stopped.
+pub fn ka_sound_emitters_update(world: &mut World, game: &mut KaGame, camera: &KaCamera) -> Vec<KaError> {
+    let mut errors = Vec::new();
+    let listener = camera.get_pos();
+    for (e, emitter) in world.query::<&mut KaSoundEmitter>().iter() {
+        let pos = ka_entity_pos(world, e).unwrap_or_else(Vec2::zero) + emitter.offset;
+        let (volume, pan) = emitter.spatial.get_volume_pan(pos, listener);
+
+        if let Some(handle) = emitter.handle {
+            if !game.audio.is_playing(handle) {
+                //Ended by itself.
+                emitter.handle = None;
+                emitter.playing = false;
+                if emitter.despawn_when_done {
+                    game.to_despawn(e);
+                }
+                continue;
+            }
+            if !emitter.playing {
+                game.stop_sound(handle);
+                emitter.handle = None;
+                continue;
+            }
+            game.audio.set_volume(handle, emitter.options.volume * volume);
+            game.audio.set_pan(handle, pan);
+        } else if emitter.playing {
+            let options = KaPlayOptions {
+                volume: emitter.options.volume * volume,
+                pan: Some(pan),
+                ..emitter.options.clone()
+            };
+            match game.play_sound_with(&emitter.sound, &options) {
+                Ok(handle) => emitter.handle = Some(handle),
+                Err(err) => {
+                    errors.push(err);
+                    emitter.playing = false;
+                },
+            }
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_rolloffs() -> Vec<KaRolloff> {
+        vec![KaRolloff::Linear, KaRolloff::Inverse, KaRolloff::Exponential(2.0), KaRolloff::Custom(|t| 1.0 - t * t)]
+    }
+
+    #[test]
+    fn volume_is_continuous_at_the_distances() {
+        for rolloff in get_rolloffs() {
+            let spatial = KaSpatial {rolloff, ..Default::default()};
+            let (min, max) = (spatial.min_distance, spatial.max_distance);
+            assert!((spatial.get_volume(min + 0.01) - 1.0).abs() < 0.01, "{:?} at min_distance", rolloff);
+            assert!(spatial.get_volume(max - 0.01) < 0.01, "{:?} at max_distance", rolloff);
+            assert_eq!(spatial.get_volume(max), 0.0);
+        }
+    }
+
+    #[test]
+    fn volume_goes_down_with_distance() {
+        for rolloff in get_rolloffs() {
+            let spatial = KaSpatial {rolloff, ..Default::default()};
+            let mut last = 1.0;
+            for i in 0..=100 {
+                let volume = spatial.get_volume(i as f32 * 8.0);
+                assert!(volume <= last, "{:?} goes up at {}", rolloff, i as f32 * 8.0);
+                last = volume;
+            }
+        }
+    }
+}
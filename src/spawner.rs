@@ -1,17 +1,23 @@
 use hecs::{World, EntityBuilder};
 
+use crate::KaSpawnOrder;
+
 pub struct Spawner {
     builders: Vec<EntityBuilder>,
+    next_order: u64,
 }
 
 impl Spawner {
     pub fn new() -> Self {
         Self {
             builders: Vec::new(),
+            next_order: 0,
         }
     }
 
-    pub fn add(&mut self, builder: EntityBuilder) {
+    pub fn add(&mut self, mut builder: EntityBuilder) {
+        builder.add(KaSpawnOrder(self.next_order));
+        self.next_order += 1;
         self.builders.push(builder);
     }
 
@@ -1,56 +1,232 @@
-use macroquad::prelude::{Vec2, draw_texture_ex, Color, Texture2D, Rect};
+use macroquad::prelude::{Vec2, draw_texture_ex, Color, Texture2D, Rect, WHITE};
 
 use hecs::{World, Entity};
 
+use crate::{KaGame, KaMaterial, KaUniform, Handle};
+
+#[derive(Clone)]
 pub struct KaSprite {
     pub visible: bool,
     pub pos: Vec2,
+    //In world units, with a frame it is multiplied by scale / 2.0 like the animated sprites always did.
     pub offset: Vec2,
     pub scale: Vec2,
-    pub texture: Texture2D,
+    //Resolved when drawing, the sprite is not drawn after its texture is unloaded.
+    pub texture: Handle<Texture2D>,
     pub color: Color,
     pub z: u32,
     pub rotation: f32,
     pub frame: Option<Rect>,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    //Rotation pivot relative to pos, None rotates around the center of the sprite.
+    pub pivot: Option<Vec2>,
+    //Index of the layer in KaRenderLayers, only used by ka_draw_layers.
+    pub layer: usize,
+    pub material: Option<KaMaterial>,
+    //Set before drawing this sprite and restored after it. Every sprite with its own
+    //uniforms is drawn in its own draw call, so use them only when needed.
+    pub uniforms: Vec<(String, KaUniform)>,
     
 }
 
-pub fn ka_draw_sprites(world: &mut World) {
-    use macroquad::prelude::DrawTextureParams;
-    let mut q = world.query::<&KaSprite>();
-    let mut query: Vec<(Entity, &KaSprite)> = q.iter().collect();
+impl KaSprite {
+    pub fn new(texture: Handle<Texture2D>) -> Self {
+        Self {
+            visible: true,
+            pos: Vec2::zero(),
+            offset: Vec2::zero(),
+            scale: Vec2::one(),
+            texture,
+            color: WHITE,
+            z: 0,
+            rotation: 0.0,
+            frame: None,
+            flip_x: false,
+            flip_y: false,
+            pivot: None,
+            layer: 0,
+            material: None,
+            uniforms: Vec::new(),
+        }
+    }
 
-    query.sort_by(|a, b| a.1.z.partial_cmp(&b.1.z).unwrap() );
-    for (_, sprite) in query.iter() {
-        if sprite.visible {
-            match sprite.frame {
-                Some(frame) => {
-                    let (x, y) = (sprite.pos.x() + (sprite.offset.x() - frame.w) * sprite.scale.x() / 2.0 , sprite.pos.y() + (sprite.offset.y() - frame.h) * sprite.scale.y() / 2.0);
-                    let (x,y) = (x.floor(), y.floor());
-                    let params = DrawTextureParams {
-                        dest_size: Some(Vec2::new(frame.w * sprite.scale.x(), frame.h * sprite.scale.y())),
-                        source: sprite.frame,
-                        rotation: sprite.rotation,
-                        pivot: None,
-                    };
-                    draw_texture_ex(sprite.texture, x, y, sprite.color, params);
-    
-                },
-                None => {
-                    
-                    let (x, y) = (sprite.pos.x() + sprite.offset.x() - sprite.texture.width() * sprite.scale.x() / 2.0 , sprite.pos.y() + sprite.offset.y() - sprite.texture.height() * sprite.scale.y() / 2.0);
-                    let params = DrawTextureParams {
-                        dest_size: Some(Vec2::new(sprite.texture.width() * sprite.scale.x(), sprite.texture.height() * sprite.scale.y())),
-                        source: sprite.frame,
-                        rotation: sprite.rotation,
-                        pivot: None,
-                    };
-                    draw_texture_ex(sprite.texture, x, y, sprite.color, params);
-                }
+    //0 is the default material.
+    pub fn get_material_id(&self) -> usize {
+        match &self.material {
+            Some(material) => material.id,
+            None => 0,
+        }
+    }
+
+    pub fn set_uniform(&mut self, name: &str, value: KaUniform) {
+        match self.uniforms.iter_mut().find(|(n, _)| n == name) {
+            Some(uniform) => uniform.1 = value,
+            None => self.uniforms.push((name.to_owned(), value)),
+        }
+    }
+
+    //texture is the one of the handle, from KaGame::get_texture_from_handle.
+    pub fn get_size(&self, texture: &Texture2D) -> Vec2 {
+        match self.frame {
+            Some(frame) => Vec2::new(frame.w, frame.h),
+            None => Vec2::new(texture.width(), texture.height()),
+        }
+    }
+
+    //The source rect with the flips applied, a negative width or height mirrors the uvs.
+    pub fn get_source(&self, texture: &Texture2D) -> Rect {
+        let mut source = match self.frame {
+            Some(frame) => frame,
+            None => Rect::new(0.0, 0.0, texture.width(), texture.height()),
+        };
+        if self.flip_x {
+            source.x += source.w;
+            source.w = -source.w;
+        }
+        if self.flip_y {
+            source.y += source.h;
+            source.h = -source.h;
+        }
+        source
+    }
+
+    //Top left corner where the sprite is drawn.
+    pub fn get_draw_pos(&self, texture: &Texture2D) -> Vec2 {
+        let size = self.get_size(texture);
+        let offset = match self.frame {
+            Some(_) => self.offset * self.scale / 2.0,
+            None => self.offset,
+        };
+        let x = self.pos.x() + offset.x() - size.x() * self.scale.x() / 2.0;
+        let y = self.pos.y() + offset.y() - size.y() * self.scale.y() / 2.0;
+        Vec2::new(x.floor(), y.floor())
+    }
+}
+
+//Animates the color of a KaSprite from one color to another.
+pub struct KaTintAnimation {
+    pub from: Color,
+    pub to: Color,
+    pub duration: f32,
+    pub timer: f32,
+    pub replay: bool,
+    //Used with replay to go back and forth between the colors.
+    pub ping_pong: bool,
+}
+
+impl KaTintAnimation {
+    pub fn new(from: Color, to: Color, duration: f32, replay: bool) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            timer: 0.0,
+            replay,
+            ping_pong: false,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.replay && self.timer >= self.duration
+    }
+
+    pub fn get_color(&self) -> Color {
+        if self.duration <= 0.0 {
+            return self.to;
+        }
+        let mut t = (self.timer / self.duration).min(1.0);
+        if self.ping_pong {
+            t = 1.0 - (1.0 - 2.0 * t).abs();
+        }
+        let mut color = self.from;
+        for i in 0..4 {
+            let (a, b) = (self.from.0[i] as f32, self.to.0[i] as f32);
+            color.0[i] = (a + (b - a) * t).round() as u8;
+        }
+        color
+    }
+}
+
+pub fn ka_sprite_tint(world: &mut World, delta: f32) {
+    for (_, (sprite, tint)) in world.query::<(&mut KaSprite, &mut KaTintAnimation)>().iter() {
+        tint.timer += delta;
+        if tint.replay && tint.duration > 0.0 {
+            while tint.timer >= tint.duration {
+                tint.timer -= tint.duration;
             }
         }
+        sprite.color = tint.get_color();
+    }
+}
 
-        
+//Does nothing when the texture of the sprite was unloaded.
+pub fn ka_draw_sprite(game: &KaGame, sprite: &KaSprite) {
+    if let Some(texture) = game.get_texture_from_handle(sprite.texture) {
+        draw_sprite_texture(sprite, texture);
+    }
+}
+
+//texture is the resolved handle of the sprite.
+pub(crate) fn draw_sprite_texture(sprite: &KaSprite, texture: Texture2D) {
+    use macroquad::prelude::DrawTextureParams;
+    if sprite.visible {
+        let pos = sprite.get_draw_pos(&texture);
+        let size = sprite.get_size(&texture);
+        let params = DrawTextureParams {
+            dest_size: Some(Vec2::new(size.x() * sprite.scale.x(), size.y() * sprite.scale.y())),
+            source: Some(sprite.get_source(&texture)),
+            rotation: sprite.rotation,
+            pivot: sprite.pivot.map(|pivot| sprite.pos + pivot),
+        };
+        draw_texture_ex(texture, pos.x(), pos.y(), sprite.color, params);
     }
 }
 
+//Draws the sprites in order, changing the material only when it is different from the previous sprite.
+//The uniforms of a sprite are restored to the values of the material for the next sprite.
+pub fn ka_draw_sprite_list(game: &KaGame, sprites: &[(Entity, &KaSprite)]) {
+    use macroquad::prelude::{gl_use_material, gl_use_default_material};
+    let mut current: Option<&KaMaterial> = None;
+    //Uniforms of the previous sprite, still applied to the current material.
+    let mut applied: &[(String, KaUniform)] = &[];
+    for (_, sprite) in sprites.iter() {
+        if !sprite.visible {
+            continue;
+        }
+        let id = sprite.get_material_id();
+        if id != current.map_or(0, |m| m.id) {
+            if let Some(material) = current {
+                material.swap_uniforms(applied, &[]);
+            }
+            applied = &[];
+            match &sprite.material {
+                Some(material) => gl_use_material(material.material),
+                None => gl_use_default_material(),
+            }
+            current = sprite.material.as_ref();
+        }
+        if let Some(material) = current {
+            material.swap_uniforms(applied, &sprite.uniforms);
+            applied = &sprite.uniforms;
+        }
+        ka_draw_sprite(game, sprite);
+    }
+    if let Some(material) = current {
+        material.swap_uniforms(applied, &[]);
+        gl_use_default_material();
+    }
+}
+
+pub fn ka_draw_sprites(world: &mut World, game: &KaGame) {
+    let mut q = world.query::<&KaSprite>();
+    let mut query: Vec<(Entity, &KaSprite)> = q.iter().collect();
+
+    //The entity id breaks the ties so equal z does not flicker between frames,
+    //the material groups the sprites of the same z so they can be batched.
+    query.sort_by(|a, b| a.1.z.cmp(&b.1.z)
+        .then(a.1.get_material_id().cmp(&b.1.get_material_id()))
+        .then(a.0.id().cmp(&b.0.id()))
+    );
+    ka_draw_sprite_list(game, &query);
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use macroquad::prelude::Vec2;
+use hecs::{World, Entity};
+
+use crate::{KaSprite, KaAABB, ka_entity_pos};
+
+//Max depth of a hierarchy, it also protects us from cycles.
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KaTransform {
+    pub pos: Vec2,
+    pub rotation: f32,
+    pub scale: Vec2,
+    pub flip_x: bool,
+    pub flip_y: bool,
+}
+
+impl Default for KaTransform {
+    fn default() -> Self {
+        Self {
+            pos: Vec2::zero(),
+            rotation: 0.0,
+            scale: Vec2::one(),
+            flip_x: false,
+            flip_y: false,
+        }
+    }
+}
+
+impl KaTransform {
+    pub fn new(x: f32, y: f32) -> Self {
+        Self {
+            pos: Vec2::new(x, y),
+            ..Default::default()
+        }
+    }
+
+    pub fn from_sprite(sprite: &KaSprite) -> Self {
+        Self {
+            pos: sprite.pos,
+            rotation: sprite.rotation,
+            scale: sprite.scale,
+            flip_x: sprite.flip_x,
+            flip_y: sprite.flip_y,
+        }
+    }
+
+    //Returns the local transform in the space of self.
+    pub fn combine(&self, local: &KaTransform) -> KaTransform {
+        let mut offset = local.pos * self.scale;
+        if self.flip_x {
+            *offset.x_mut() = -offset.x();
+        }
+        if self.flip_y {
+            *offset.y_mut() = -offset.y();
+        }
+        let (sin, cos) = self.rotation.sin_cos();
+        let offset = Vec2::new(offset.x() * cos - offset.y() * sin, offset.x() * sin + offset.y() * cos);
+        //A mirrored parent turns its children the other way.
+        let rotation = if self.flip_x != self.flip_y {-local.rotation} else {local.rotation};
+        KaTransform {
+            pos: self.pos + offset,
+            rotation: self.rotation + rotation,
+            scale: self.scale * local.scale,
+            flip_x: self.flip_x != local.flip_x,
+            flip_y: self.flip_y != local.flip_y,
+        }
+    }
+}
+
+//Makes an entity follow its parent. The KaSprite and KaAABB of the child are overwritten
+//with the world transform, so do not use it with a KaMoveable.
+pub struct KaChild {
+    pub parent: Entity,
+    pub local: KaTransform,
+}
+
+impl KaChild {
+    pub fn new(parent: Entity, x: f32, y: f32) -> Self {
+        Self {
+            parent,
+            local: KaTransform::new(x, y),
+        }
+    }
+}
+
+fn get_world_transform(world: &World, entity: Entity, resolved: &mut HashMap<Entity, KaTransform>, depth: usize) -> Option<KaTransform> {
+    if let Some(transform) = resolved.get(&entity) {
+        return Some(*transform);
+    }
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    
+    let transform = if let Ok(child) = world.get::<KaChild>(entity) {
+        let parent = get_world_transform(world, child.parent, resolved, depth + 1)?;
+        parent.combine(&child.local)
+    } else {
+        //Same position as the camera and the other systems, the rest comes from the sprite.
+        let pos = ka_entity_pos(world, entity)?;
+        let mut transform = match world.get::<KaSprite>(entity) {
+            Ok(sprite) => KaTransform::from_sprite(&sprite),
+            Err(_) => KaTransform::default(),
+        };
+        transform.pos = pos;
+        transform
+    };
+    
+    resolved.insert(entity, transform);
+    Some(transform)
+}
+
+//Resolves the world transform of every KaChild. Children whose parent is gone keep their last transform.
+pub fn ka_transforms_update(world: &mut World) {
+    let mut resolved: HashMap<Entity, KaTransform> = HashMap::new();
+    let children: Vec<Entity> = world.query::<&KaChild>().iter().map(|(e, _)| e).collect();
+    for e in children.iter() {
+        get_world_transform(world, *e, &mut resolved, 0);
+    }
+
+    for (e, (_, sprite)) in world.query::<(&KaChild, &mut KaSprite)>().iter() {
+        if let Some(transform) = resolved.get(&e) {
+            sprite.pos = transform.pos;
+            sprite.rotation = transform.rotation;
+            sprite.scale = transform.scale;
+            sprite.flip_x = transform.flip_x;
+            sprite.flip_y = transform.flip_y;
+        }
+    }
+
+    for (e, (_, aabb)) in world.query::<(&KaChild, &KaAABB)>().iter() {
+        if let Some(transform) = resolved.get(&e) {
+            *aabb.pos.lock().unwrap() = transform.pos;
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use macroquad::prelude::{
+    Vec2, Rect, Color, BLACK, RenderTarget, FilterMode, DrawTextureParams,
+    render_target, set_texture_filter, set_camera, set_default_camera,
+    clear_background, draw_texture_ex, screen_width, screen_height, WHITE,
+};
+
+use crate::KaCamera;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KaScaleMode {
+    //Pixel perfect, only whole multiples of the virtual resolution.
+    Integer,
+    //Keeps the aspect with bars.
+    Letterbox,
+    Stretch,
+}
+
+//Renders the game to an offscreen target of a fixed resolution and draws it scaled to the window.
+pub struct KaVirtualScreen {
+    width: u32,
+    height: u32,
+    pub target: RenderTarget,
+    pub mode: KaScaleMode,
+    //Color of the bars.
+    pub clear_color: Color,
+}
+
+impl KaVirtualScreen {
+    pub fn new(width: u32, height: u32, mode: KaScaleMode) -> Self {
+        let target = render_target(width, height);
+        set_texture_filter(target.texture, FilterMode::Nearest);
+        Self {
+            width,
+            height,
+            target,
+            mode,
+            clear_color: BLACK,
+        }
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    //Pixel rect of the window where the virtual screen is drawn.
+    pub fn get_screen_rect(&self, screen_width: f32, screen_height: f32) -> Rect {
+        let (w, h) = (self.width as f32, self.height as f32);
+        let scale = match self.mode {
+            KaScaleMode::Stretch => return Rect::new(0.0, 0.0, screen_width, screen_height),
+            KaScaleMode::Letterbox => (screen_width / w).min(screen_height / h),
+            KaScaleMode::Integer => (screen_width / w).min(screen_height / h).floor().max(1.0),
+        };
+        let (w, h) = (w * scale, h * scale);
+        Rect::new(((screen_width - w) / 2.0).floor(), ((screen_height - h) / 2.0).floor(), w, h)
+    }
+
+    //Sets the camera to draw in the virtual screen.
+    pub fn begin(&self, camera: &KaCamera) {
+        set_camera(camera.get_target_camera(self.target));
+    }
+
+    //Draws the virtual screen in the window.
+    pub fn end(&self) {
+        set_default_camera();
+        clear_background(self.clear_color);
+        let rect = self.get_screen_rect(screen_width(), screen_height());
+        let params = DrawTextureParams {
+            dest_size: Some(Vec2::new(rect.w, rect.h)),
+            ..Default::default()
+        };
+        draw_texture_ex(self.target.texture, rect.x, rect.y, WHITE, params);
+    }
+
+    //Window pixels to virtual screen pixels.
+    pub fn screen_to_virtual(&self, pos: (f32, f32)) -> Vec2 {
+        let rect = self.get_screen_rect(screen_width(), screen_height());
+        Vec2::new(
+            (pos.0 - rect.x) / rect.w * self.width as f32,
+            (pos.1 - rect.y) / rect.h * self.height as f32,
+        )
+    }
+
+    //Window pixels (like mouse_position) to world coordinates of a camera drawn in the virtual screen.
+    pub fn screen_to_world(&self, camera: &KaCamera, pos: (f32, f32)) -> Vec2 {
+        let rect = self.get_screen_rect(screen_width(), screen_height());
+        camera.screen_to_world_in(Vec2::new(pos.0, pos.1), rect)
+    }
+
+    pub fn world_to_screen(&self, camera: &KaCamera, pos: Vec2) -> Vec2 {
+        let rect = self.get_screen_rect(screen_width(), screen_height());
+        camera.world_to_screen_in(pos, rect)
+    }
+}